@@ -0,0 +1,405 @@
+//! Play a quiz pack in the terminal, to rehearse it or try the rules without Discord.
+//!
+//! `answer-bot-sim [--pack FILE] [--script FILE] [--tolerance N] [--flat] [--tie-breaker NAME]`
+//!
+//! Every line of the script, or of the standard input without one, is either:
+//! - `/team TEAM`, a team joining the game
+//! - `TEAM PLAYER: GUESS`, a guess sent by `PLAYER` in the channel of `TEAM`
+//! - `/accept TEAM PLAYER: ANSWER`, an admin counting a guess as `ANSWER`
+//! - `/skip`, `/pause`, `/resume` or `/end`
+//!
+//! The game starts at the first line that isn't `/team`, teams and players also join the
+//! first time they appear. Empty lines and lines starting with `#` are ignored. The
+//! messages of the bot are written the way the game sends them, followed by who sees them
+//! when it isn't everyone.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use answer_bot::blindtest::{self, LateStart, Source};
+use answer_bot::config::{GameState, GuildConfig, Question, Team};
+use answer_bot::engine::{self, Input};
+use answer_bot::results::TieBreaker;
+use answer_bot::settings::{GuildSettings, Scoring};
+use answer_bot::templates::{self, Template};
+use answer_bot::utils::{Outbound, Outbox};
+use answer_bot::Error;
+use poise::ChoiceParameter;
+use serenity::all::{ChannelId, Message, MessageId, Timestamp, UserId};
+
+/// Id of the admin channel and of the admin, the teams and the players are numbered from 1
+const ADMIN: u64 = u64::MAX;
+
+enum Line {
+    Team(String),
+    Guess {
+        team: String,
+        player: String,
+        text: String,
+        accept: bool,
+    },
+    Admin(Input),
+}
+
+impl Line {
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let line = match line {
+            "/skip" => Self::Admin(Input::Skip),
+            "/pause" => Self::Admin(Input::Pause),
+            "/resume" => Self::Admin(Input::Resume),
+            "/end" => Self::Admin(Input::End),
+            _ => {
+                if let Some(team) = line.strip_prefix("/team ") {
+                    return Ok(Some(Self::Team(team.trim().to_owned())));
+                }
+                let (accept, rest) = match line.strip_prefix("/accept ") {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let unreadable = || format!("Can't read `{line}`");
+                let (who, text) = rest.split_once(':').ok_or_else(unreadable)?;
+                let mut who = who.split_whitespace();
+                let (Some(team), Some(player), None) = (who.next(), who.next(), who.next()) else {
+                    return Err(unreadable());
+                };
+                Self::Guess {
+                    team: team.to_owned(),
+                    player: player.to_owned(),
+                    text: text.trim().to_owned(),
+                    accept,
+                }
+            }
+        };
+        Ok(Some(line))
+    }
+}
+
+struct Player {
+    name: String,
+    team: String,
+}
+
+/// A game played from text lines, writing what the bot would send
+struct Simulator {
+    game: GuildConfig,
+    /// The id of a player is its index plus one
+    players: Vec<Player>,
+    messages: u64,
+}
+
+impl Simulator {
+    fn new(questions: Vec<Question>, settings: GuildSettings) -> Self {
+        Self {
+            game: GuildConfig::new(0, ChannelId::new(ADMIN), questions, settings),
+            players: Vec::new(),
+            messages: 0,
+        }
+    }
+
+    /// Channel of the team named `name`, the team joining the game if it's new
+    fn team(&mut self, name: &str, outbox: &mut Outbox) -> Result<ChannelId, String> {
+        if let Some(team) = self.game.teams.iter().find(|t| t.name == name) {
+            return Ok(team.channel);
+        }
+        let channel = ChannelId::new(self.game.teams.len() as u64 + 1);
+        let team = Team {
+            name: name.to_owned(),
+            leaderboard: HashMap::new(),
+            total_points: 0.0,
+            channel,
+            role: None,
+            disqualified: None,
+        };
+        blindtest::add_team(
+            &mut self.game,
+            outbox,
+            team,
+            LateStart::Zero,
+            UserId::new(ADMIN),
+        )?;
+        Ok(channel)
+    }
+
+    /// The message `player` sends in the channel of `team`
+    fn message(
+        &mut self,
+        team: &str,
+        player: &str,
+        text: String,
+        outbox: &mut Outbox,
+    ) -> Result<Message, String> {
+        // A rejected line must not make anyone join
+        if let Some(other) = self
+            .players
+            .iter()
+            .find(|p| p.name == player && p.team != team)
+        {
+            return Err(format!("`{player}` already plays for `{}`", other.team));
+        }
+        let channel = self.team(team, outbox)?;
+        let id = match self.players.iter().position(|p| p.name == player) {
+            Some(id) => id,
+            None => {
+                self.players.push(Player {
+                    name: player.to_owned(),
+                    team: team.to_owned(),
+                });
+                self.players.len() - 1
+            }
+        };
+        self.messages += 1;
+        let mut message = Message::default();
+        message.id = MessageId::new(self.messages);
+        message.channel_id = channel;
+        message.author.id = UserId::new(id as u64 + 1);
+        message.content = text;
+        message.timestamp = Timestamp::now();
+        Ok(message)
+    }
+
+    /// Feed a line to the game like the bot would
+    fn play(&mut self, line: Line, outbox: &mut Outbox) -> Result<(), String> {
+        let admin = UserId::new(ADMIN);
+        let rules = self.game.settings.rules();
+        if !matches!(line, Line::Team(_))
+            && matches!(self.game.engine.state, GameState::Configuring)
+        {
+            let effects = self.game.engine.handle(Input::Start, rules)?;
+            blindtest::apply(&mut self.game, outbox, effects, Source::Admin(admin));
+            // The admin starting the game is told about the first question
+            outbox.admin(templates::embed(
+                &self.game.settings,
+                Template::NextQuestion,
+                &templates::next_question_args(&self.game),
+            ));
+        }
+        match line {
+            Line::Team(name) => {
+                self.team(&name, outbox)?;
+            }
+            Line::Guess {
+                team,
+                player,
+                text,
+                accept,
+            } => {
+                let message = self.message(&team, &player, text, outbox)?;
+                if accept {
+                    blindtest::accept(&mut self.game, outbox, &message, &message.content, admin)?;
+                } else {
+                    blindtest::guess(&mut self.game, outbox, &message, &message.content, &[]);
+                }
+            }
+            Line::Admin(Input::Skip) => blindtest::skip(&mut self.game, outbox, admin)?,
+            Line::Admin(Input::Pause) => blindtest::pause(&mut self.game, outbox, admin, true)?,
+            Line::Admin(Input::Resume) => blindtest::pause(&mut self.game, outbox, admin, false)?,
+            Line::Admin(input) => {
+                let effects = self.game.engine.handle(input, rules)?;
+                blindtest::apply(&mut self.game, outbox, effects, Source::Admin(admin));
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of a player or of a channel mentioned as `@ID` or `#ID`
+    fn mention(&self, mention: &str) -> Option<String> {
+        if let Some(id) = mention.strip_prefix('@') {
+            let id: usize = id.parse().ok()?;
+            return Some(self.players.get(id.checked_sub(1)?)?.name.clone());
+        }
+        let id: u64 = mention.strip_prefix('#')?.parse().ok()?;
+        if id == ADMIN {
+            return Some(String::from("#admins"));
+        }
+        let team = self.game.teams.iter().find(|t| t.channel.get() == id)?;
+        Some(format!("#{}", team.name))
+    }
+
+    /// `text` with the Discord mentions replaced by names
+    fn names(&self, text: &str) -> String {
+        let mut s = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('<') {
+            s.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let mention = rest
+                .find('>')
+                .and_then(|end| Some((self.mention(&rest[1..end])?, end)));
+            match mention {
+                Some((name, end)) => {
+                    s.push_str(&name);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    s.push('<');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        s.push_str(rest);
+        s
+    }
+
+    /// Who sees a message sent to `channels`
+    fn audience(&self, channels: &[ChannelId]) -> String {
+        if channels.len() > self.game.teams.len() {
+            return String::new();
+        }
+        let names: Vec<_> = channels
+            .iter()
+            .filter_map(|c| self.mention(&format!("#{c}")))
+            .collect();
+        format!(" ({})", names.join(", "))
+    }
+
+    /// Write the messages of the bot
+    fn render(&self, outbox: &Outbox, out: &mut String) -> Result<(), Error> {
+        for outbound in outbox.queued() {
+            match outbound {
+                Outbound::Send(channels, messages) => {
+                    let audience = self.audience(channels);
+                    for message in messages {
+                        let message = serde_json::to_value(message)?;
+                        for embed in message["embeds"].as_array().into_iter().flatten() {
+                            let title = embed["title"].as_str().unwrap_or_default();
+                            writeln!(out, "## {}{audience}", self.names(title))?;
+                            if let Some(description) = embed["description"].as_str() {
+                                writeln!(out, "{}", self.names(description))?;
+                            }
+                        }
+                    }
+                }
+                Outbound::React(_, _, emoji) => writeln!(out, "{emoji}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Play every line of `input`, ending the game if it's still running at the end
+    fn run(&mut self, input: impl BufRead, mut out: impl Write) -> Result<(), Error> {
+        for line in input.lines() {
+            if matches!(self.game.engine.state, GameState::Ended) {
+                return Ok(());
+            }
+            let line = line?;
+            out.write_all(self.play_line(Line::parse(&line))?.as_bytes())?;
+            out.flush()?;
+        }
+        if !matches!(self.game.engine.state, GameState::Ended) {
+            out.write_all(
+                self.play_line(Ok(Some(Line::Admin(Input::End))))?
+                    .as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn play_line(&mut self, line: Result<Option<Line>, String>) -> Result<String, Error> {
+        let mut outbox = Outbox::new(&self.game);
+        let mut text = String::new();
+        if let Err(e) = line.and_then(|line| match line {
+            Some(line) => self.play(line, &mut outbox),
+            None => Ok(()),
+        }) {
+            writeln!(text, "Error! {e}")?;
+        }
+        self.render(&outbox, &mut text)?;
+        Ok(text)
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let mut pack = PathBuf::from(engine::DEFAULT_PACK);
+    let mut script = None;
+    let mut settings = GuildSettings::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pack" => pack = args.next().ok_or("--pack needs a file")?.into(),
+            "--script" => script = Some(args.next().ok_or("--script needs a file")?),
+            "--tolerance" => {
+                settings.tolerance = args.next().ok_or("--tolerance needs a number")?.parse()?
+            }
+            "--flat" => settings.scoring = Scoring::Flat,
+            "--tie-breaker" => {
+                let name = args.next().ok_or("--tie-breaker needs a name")?;
+                settings.tie_breaker = TieBreaker::from_name(&name)
+                    .ok_or_else(|| format!("Unknown tie-breaker `{name}`"))?;
+            }
+            _ => return Err(format!("Unknown argument `{arg}`").into()),
+        }
+    }
+    let mut simulator = Simulator::new(engine::load_questions(&pack)?, settings);
+    let out = std::io::stdout().lock();
+    match script {
+        Some(script) => {
+            let file = std::fs::File::open(script)?;
+            simulator.run(std::io::BufReader::new(file), out)
+        }
+        None => simulator.run(std::io::stdin().lock(), out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use answer_bot::config::{prepare_questions, Answer};
+
+    fn simulate(script: &str) -> String {
+        let questions = ["first", "second"]
+            .iter()
+            .map(|name| Question {
+                name: (*name).to_owned(),
+                answer: vec![
+                    Answer::SingleAnswer(format!("{name} song")),
+                    Answer::SingleAnswer(format!("{name} band")),
+                ],
+                ..Default::default()
+            })
+            .collect();
+        let mut out = Vec::new();
+        Simulator::new(prepare_questions(questions), GuildSettings::default())
+            .run(script.as_bytes(), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plays_a_whole_game() {
+        let out = simulate(
+            "/team red\n\
+             /team blue\n\
+             red alice: first song\n\
+             # a comment\n\
+             blue bob: first band\n\
+             /skip\n\
+             red alice: this is ignored\n",
+        );
+        assert!(out.contains("We will soon start the blindtest"));
+        assert!(out.contains("alice found an answer !\nIt was: `first song`"));
+        assert!(out.contains("## Next question ! (#admins)\n"));
+        assert!(out.contains("The game is finished"));
+        // Tied teams share the same rank, like at the end of a game on Discord
+        assert!(out.contains("🥇 `red` => 1pts\n🥇 `blue` => 1pts"));
+        assert!(out.contains("🥇 alice (`red`) => 1pts, 1 answers"));
+        assert!(!out.contains("this is ignored"));
+    }
+
+    #[test]
+    fn reports_the_lines_it_cant_play() {
+        let out = simulate("nonsense\nred alice: x\nblue alice: x\n/resume\n");
+        assert!(out.contains("Error! Can't read `nonsense`"));
+        assert!(out.contains("Error! `alice` already plays for `red`"));
+        assert!(out.contains("Error! The game isn't paused"));
+        // The team of the rejected guess didn't join the game
+        assert!(!out.contains("`blue`"));
+        // The game still running at the end of the script is ended
+        assert!(out.contains("## Podium"));
+    }
+}
@@ -1,40 +1,26 @@
-use rand::prelude::*;
 use std::collections::HashMap;
 
 use serenity::all::Mentionable;
 
+use crate::buzzer;
 use crate::config;
 use crate::config::GameState;
+use crate::engine::{self, Effect, Input};
+use crate::events::GameEvent;
+use crate::export;
+use crate::games::Command;
+use crate::i18n;
+use crate::quiz;
+use crate::results;
+use crate::settings::{self, Tone};
+use crate::templates::{self, Template};
+use crate::tr;
 use crate::utils;
-use crate::utils::broadcast_message;
 use crate::utils::create_embed;
+use crate::utils::Outbox;
 use crate::Context;
 use crate::Error;
 
-fn load_questions() -> Result<Vec<config::Question>, Error> {
-    let mut path = std::path::PathBuf::from("./");
-    path.push("blind_test");
-    path.push("music.json");
-    let file = std::fs::File::open(&path)?;
-    let mut out: Vec<config::Question> = serde_json::from_reader(&file)?;
-    out.iter_mut()
-        .flat_map(|s| s.answer.iter_mut())
-        .flat_map(|s| match s {
-            config::Answer::MutlipleAnswer(s, len) => {
-                *len = s.len();
-                either::Left(s.iter_mut())
-            }
-            config::Answer::SingleAnswer(s) => either::Right(std::iter::once(s)),
-        })
-        .for_each(|s| {
-            let taken = std::mem::take(s);
-            *s = config::Question::normalize_string(&taken);
-        });
-    out.reverse();
-    //out.shuffle(&mut rand::thread_rng());
-    Ok(out)
-}
-
 /// Command to interact with the games
 #[poise::command(
     slash_command,
@@ -43,6 +29,11 @@ fn load_questions() -> Result<Vec<config::Question>, Error> {
         "end_game",
         "delete_game",
         "start_game",
+        "skip_question",
+        "pause_game",
+        "resume_game",
+        "export_log",
+        "export_results",
         "team_cmd",
         "points_cmd"
     ),
@@ -56,7 +47,18 @@ pub async fn game_cmd(_ctx: Context<'_>) -> Result<(), Error> {
 /// Command to interact with the teams of a game
 #[poise::command(
     slash_command,
-    subcommands("add_team_game", "remove_team_game"),
+    subcommands(
+        "add_team_game",
+        "remove_team_game",
+        "join_team",
+        "crate::teams::create_teams",
+        "crate::teams::cleanup_teams",
+        "crate::teams::signup_teams",
+        "crate::teams::balance_teams",
+        "crate::teams::disqualify_team",
+        "crate::teams::rename_team",
+        "crate::teams::move_team"
+    ),
     rename = "teams",
     guild_only
 )]
@@ -67,7 +69,7 @@ pub async fn team_cmd(_ctx: Context<'_>) -> Result<(), Error> {
 /// Get the leaderboard !
 #[poise::command(
     slash_command,
-    subcommands("points_list"),
+    subcommands("points_list", "points_give", "points_take", "points_accept"),
     rename = "points",
     guild_only
 )]
@@ -76,67 +78,356 @@ pub async fn points_cmd(_ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Get the leaderboard !
-#[poise::command(slash_command, rename = "list", guild_only)]
+#[poise::command(slash_command, rename = "list", guild_only, category = "Player")]
 pub async fn points_list(
     ctx: Context<'_>,
-    #[description = "also output a json for the leaderboard"]
+    #[description = "also attach a json file with the results"]
     #[rename = "json"]
     print_json: bool,
+    #[description = "how to order teams and players with the same points, the game's one by default"]
+    tie_breaker: Option<results::TieBreaker>,
 ) -> Result<(), Error> {
-    let data = ctx.data().games.write().await;
-    if !data.contains_key(&ctx.guild_id().unwrap()) {
-        utils::send_error(ctx, "This guild doesn't has a g ame").await?;
+    const PER_PAGE: usize = 15;
+    let Some(game) = ctx.data().games.get(ctx.guild_id().unwrap()).await? else {
+        utils::send_error(ctx, "This guild doesn't has a game").await?;
         return Ok(());
-    }
-    for team in data
-        .get(&ctx.guild_id().unwrap())
-        .map(|c| c.teams.clone())
-        .unwrap()
+    };
+    let tie_breaker = tie_breaker.unwrap_or(game.settings.tie_breaker);
+    let lang = game.settings.language;
+    let mut pages = Vec::new();
     {
         use std::fmt::Write;
-        let mut msg = String::new();
-        writeln!(
-            &mut msg,
-            "Team `{}`\ntotal {}\n",
-            team.name, team.total_points
-        )?;
-        let mut pts: Vec<_> = team.leaderboard.iter().collect();
-        pts.sort_by(|lhs, rhs| lhs.1.total_cmp(rhs.1));
-        for (i, (user, p)) in pts.into_iter().take(20).enumerate() {
-            writeln!(&mut msg, "{}) {} => {}pts\n", i + 1, user.mention(), p)?;
+        let teams = results::team_standings(&game, tie_breaker);
+        for chunk in teams.chunks(PER_PAGE) {
+            let mut msg = format!("**{}**\n", tr!(lang, "Teams"));
+            for standing in chunk {
+                writeln!(
+                    &mut msg,
+                    "{}",
+                    tr!(
+                        lang,
+                        "{rank}) `{team}` => {points}pts, {answers} answers",
+                        rank = standing.rank,
+                        team = standing.entry.name,
+                        points = standing.points,
+                        answers = standing.answers
+                    )
+                )?;
+            }
+            pages.push(msg);
         }
-        utils::send_reply(ctx, msg).await?;
-        if print_json {
-            let mut json = String::new();
-            writeln!(&mut json, "```json")?;
-            writeln!(&mut json, "{}", serde_json::to_string_pretty(&team)?)?;
-            writeln!(&mut json, "```")?;
-            utils::send_reply(ctx, json).await?;
+        let players = results::player_standings(&game, tie_breaker);
+        for chunk in players.chunks(PER_PAGE) {
+            let mut msg = format!("**{}**\n", tr!(lang, "Players"));
+            for standing in chunk {
+                let (user, team) = standing.entry;
+                writeln!(
+                    &mut msg,
+                    "{}",
+                    tr!(
+                        lang,
+                        "{rank}) {user} (`{team}`) => {points}pts, {answers} answers",
+                        rank = standing.rank,
+                        user = user.mention(),
+                        team = team.name,
+                        points = standing.points,
+                        answers = standing.answers
+                    )
+                )?;
+            }
+            pages.push(msg);
         }
     }
+    if pages.is_empty() {
+        pages.push(String::from("No team has been added"));
+    }
+    let mut reply = poise::CreateReply::default();
+    if print_json {
+        let names = export::display_names(ctx, ctx.guild_id().unwrap(), &game).await;
+        reply = reply.attachment(export::Results::new(&game, &names).json()?);
+    }
+    utils::paginate(ctx, reply, "Leaderboard", &pages).await
+}
+
+/// Export the results of the game as csv and json files
+#[poise::command(slash_command, rename = "export", guild_only)]
+pub async fn export_results(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(game) = ctx.data().games.get(ctx.guild_id().unwrap()).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+    ctx.defer().await?;
+    let names = export::display_names(ctx, ctx.guild_id().unwrap(), &game).await;
+    let results = export::Results::new(&game, &names);
+    let mut reply = poise::CreateReply::default().embed(create_embed(
+        &game.settings,
+        Tone::Success,
+        "Success!",
+        "Here are the results of the game",
+    ));
+    for file in results.csv()?.into_iter().chain([results.json()?]) {
+        reply = reply.attachment(file);
+    }
+    ctx.send(reply).await?;
     Ok(())
 }
 
-/// Show this help menu
-#[poise::command(slash_command, rename = "new", guild_only)]
-pub async fn create_game(ctx: Context<'_>) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-    if data.contains_key(&ctx.guild_id().unwrap()) {
-        utils::send_error(ctx, "This guild already has a game ongoing").await?;
+pub async fn autocomplete_team(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    ctx.data()
+        .games
+        .get(guild)
+        .await
+        .ok()
+        .flatten()
+        .as_ref()
+        .map(|g| {
+            g.teams
+                .iter()
+                .map(|t| t.name.clone())
+                .filter(|n| n.starts_with(partial))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn autocomplete_answer(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    let partial = config::Question::normalize_string(partial);
+    ctx.data()
+        .games
+        .get(guild)
+        .await
+        .ok()
+        .flatten()
+        .as_ref()
+        .and_then(|g| g.engine.current())
+        .map(|q| {
+            q.answer
+                .iter()
+                .flat_map(|a| match a {
+                    config::Answer::SingleAnswer(s) => either::Left(std::iter::once(s)),
+                    config::Answer::MutlipleAnswer(s, _) => either::Right(s.iter()),
+                })
+                .filter(|a| a.starts_with(&partial))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn adjust_points(
+    ctx: Context<'_>,
+    amount: f64,
+    reason: String,
+    team: Option<String>,
+    user: Option<serenity::all::User>,
+) -> Result<(), Error> {
+    let user = user.map(|u| u.id);
+    if team.is_none() && user.is_none() {
+        utils::send_error(ctx, "Give either a team or a user").await?;
         return Ok(());
     }
-    let questions = load_questions()?;
+    // A player that didn't answer yet is found from the roles of the teams
+    let roles = match (&team, user) {
+        (None, Some(user)) => ctx.guild_id().unwrap().member(ctx, user).await?.roles,
+        _ => Vec::new(),
+    };
+    let admin = ctx.author().id;
+    let adjusted = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            let pos = match (&team, user) {
+                (Some(name), _) => game.teams.iter().position(|t| t.name == *name),
+                (None, user) => game
+                    .teams
+                    .iter()
+                    .position(|t| user.is_some_and(|u| t.leaderboard.contains_key(&u)))
+                    .or_else(|| {
+                        game.teams
+                            .iter()
+                            .position(|t| t.role.is_some_and(|r| roles.contains(&r)))
+                    }),
+            };
+            let Some(pos) = pos else {
+                return Err("Couldn't find the team, give its name !");
+            };
+            let team = &mut game.teams[pos];
+            if let Some(user) = user {
+                *team.leaderboard.entry(user).or_default() += amount;
+            }
+            team.total_points += amount;
+            let msg = tr!(
+                game.settings.language,
+                "{amount} points for team `{team}`{user}\nReason: {reason}\nThey now have {points} points !",
+                amount = format!("{amount:+}"),
+                team = team.name,
+                user = user
+                    .map(|u| format!(" ({})", u.mention()))
+                    .unwrap_or_default(),
+                reason = reason,
+                points = team.total_points
+            );
+            let channel = team.channel;
+            let team = team.name.clone();
+            game.record_adjustment(
+                config::Adjustment {
+                    admin,
+                    team,
+                    user,
+                    amount,
+                    reason,
+                    at: serenity::all::Timestamp::now(),
+                },
+                None,
+            );
+            outbox.broadcast(
+                [channel],
+                create_embed(&game.settings, Tone::Info, "Points adjusted", &msg),
+            );
+            Ok(msg)
+        })
+        .await?;
+    match adjusted {
+        Ok(msg) => utils::send_reply(ctx, msg).await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Give points to a team or a player
+#[poise::command(slash_command, rename = "give", guild_only)]
+pub async fn points_give(
+    ctx: Context<'_>,
+    #[description = "number of points to give"]
+    #[min = 0.5]
+    amount: f64,
+    #[description = "why the points are given"] reason: String,
+    #[description = "team receiving the points"]
+    #[autocomplete = "autocomplete_team"]
+    team: Option<String>,
+    #[description = "player receiving the points"] user: Option<serenity::all::User>,
+) -> Result<(), Error> {
+    adjust_points(ctx, amount, reason, team, user).await
+}
+
+/// Take points from a team or a player
+#[poise::command(slash_command, rename = "take", guild_only)]
+pub async fn points_take(
+    ctx: Context<'_>,
+    #[description = "number of points to take"]
+    #[min = 0.5]
+    amount: f64,
+    #[description = "why the points are taken"] reason: String,
+    #[description = "team losing the points"]
+    #[autocomplete = "autocomplete_team"]
+    team: Option<String>,
+    #[description = "player losing the points"] user: Option<serenity::all::User>,
+) -> Result<(), Error> {
+    adjust_points(ctx, -amount, reason, team, user).await
+}
+
+/// Accept a rejected guess as if it matched an answer of the current question
+#[poise::command(slash_command, rename = "accept", guild_only)]
+pub async fn points_accept(
+    ctx: Context<'_>,
+    #[description = "link to the message with the guess"] message: serenity::all::Message,
+    #[description = "answer the guess counts as"]
+    #[autocomplete = "autocomplete_answer"]
+    answer: String,
+) -> Result<(), Error> {
+    let admin = ctx.author().id;
+    let author = message.author.id;
+    let accepted = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game.as_mut().filter(|g| g.engine.is_running()) else {
+                return Err(String::from("No game is running"));
+            };
+            accept(game, outbox, &message, &answer, admin)
+        })
+        .await?;
+    if let Err(e) = accepted {
+        return utils::send_error(ctx, e).await;
+    }
+    utils::send_reply(
+        ctx,
+        tr!(
+            i18n::lang(ctx),
+            "Accepted the guess of {user}",
+            user = author.mention()
+        ),
+    )
+    .await
+}
+
+/// Show this help menu
+#[poise::command(slash_command, rename = "new", guild_only)]
+pub async fn create_game(
+    ctx: Context<'_>,
+    #[description = "play in buzzer mode, giving this many seconds to the team that buzzed"]
+    #[min = 1]
+    buzzer: Option<u64>,
+    #[description = "quiz pack to play, the default questions otherwise"]
+    #[autocomplete = "quiz::autocomplete_pack"]
+    pack: Option<String>,
+) -> Result<(), Error> {
+    let questions = match pack {
+        Some(pack) => {
+            let Some(questions) = ctx
+                .data()
+                .storage
+                .quiz_pack(ctx.guild_id().unwrap(), &pack)?
+            else {
+                let msg = tr!(i18n::lang(ctx), "No quiz pack named `{pack}`", pack = pack);
+                utils::send_error(ctx, msg).await?;
+                return Ok(());
+            };
+            config::prepare_questions(questions)
+        }
+        None => engine::load_questions(std::path::Path::new(engine::DEFAULT_PACK))?,
+    };
     if questions.is_empty() {
         utils::send_error(ctx, "The questions list is empty").await?;
         return Ok(());
     }
-    let game = config::GuildConfig {
-        state: config::GameState::Configuring,
-        teams: Vec::new(),
-        admin_channel: ctx.channel_id(),
-        questions,
-    };
-    data.insert(ctx.guild_id().ok_or("Not in a guild ???")?, game);
+    let settings = ctx.data().storage.settings(ctx.guild_id().unwrap())?;
+    let mut game = config::GuildConfig::new(ctx.id(), ctx.channel_id(), questions, settings);
+    if let Some(window) = buzzer {
+        game.buzzer = Some(config::Buzzer::new(window));
+    }
+    game.log(
+        GameEvent::GameCreated {
+            admin: ctx.author().id,
+            admin_channel: ctx.channel_id(),
+            questions: game.engine.questions.len(),
+        },
+        None,
+    );
+    let guild_id = ctx.guild_id().ok_or("Not in a guild ???")?;
+    let created = ctx
+        .data()
+        .games
+        .edit(guild_id, move |current, _| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(game);
+            true
+        })
+        .await?;
+    if !created {
+        utils::send_error(ctx, "This guild already has a game ongoing").await?;
+        return Ok(());
+    }
     utils::send_reply(ctx, "Created a game in the guild !").await?;
 
     Ok(())
@@ -145,189 +436,247 @@ pub async fn create_game(ctx: Context<'_>) -> Result<(), Error> {
 /// End a game, even if there are remaining questions
 #[poise::command(slash_command, rename = "delete", guild_only)]
 pub async fn delete_game(ctx: Context<'_>) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-    let game_state = data.get(&ctx.guild_id().unwrap()).map(|c| c.state);
-    if game_state.is_none() {
-        utils::send_error(ctx, "No game existed").await?;
-        return Ok(());
-    }
-    if !matches!(game_state, Some(GameState::Ended)) {
-        utils::send_error(ctx, "The game isn't finished").await?;
-        return Ok(());
+    let deleted = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), |game, _| {
+            let Some(current) = game else {
+                return Err("No game existed");
+            };
+            if !matches!(current.engine.state, GameState::Ended) {
+                return Err("The game isn't finished");
+            }
+            if !current.provisioned.roles.is_empty() || current.provisioned.category.is_some() {
+                return Err(
+                    "Run `/game teams cleanup` first to delete the team channels and roles",
+                );
+            }
+            *game = None;
+            Ok(())
+        })
+        .await?;
+    match deleted {
+        Ok(()) => utils::send_reply(ctx, "Remove the game").await,
+        Err(e) => utils::send_error(ctx, e).await,
     }
-    data.remove(&ctx.guild_id().unwrap());
-    utils::send_reply(ctx, "Remove the game").await?;
-    Ok(())
 }
 
 /// End a game, even if there are remaining questions
 #[poise::command(slash_command, rename = "end", guild_only)]
 pub async fn end_game(ctx: Context<'_>) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-    let game_state = data.get(&ctx.guild_id().unwrap()).map(|c| c.state);
-    if game_state.is_none() {
-        utils::send_error(ctx, "No game exist").await?;
-        return Ok(());
-    }
-    if matches!(game_state, Some(GameState::Ended)) {
-        utils::send_error(ctx, "The game was already finished").await?;
-        return Ok(());
+    let admin = ctx.author().id;
+    let ended = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No game exist");
+            };
+            let effects = game.engine.handle(Input::End, game.settings.rules())?;
+            apply(game, outbox, effects, Source::Admin(admin));
+            Ok(())
+        })
+        .await?;
+    match ended {
+        Ok(()) => utils::send_reply(ctx, "Ended The game").await,
+        Err(e) => utils::send_error(ctx, e).await,
     }
-    data.get_mut(&ctx.guild_id().unwrap()).unwrap().state = GameState::Ended;
-    utils::send_reply(ctx, "Ended The game").await?;
-    Ok(())
 }
 
 /// Show this help menu
 #[poise::command(slash_command, rename = "start", guild_only)]
 pub async fn start_game(ctx: Context<'_>) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-
-    match data.get(&ctx.guild_id().unwrap()).map(|c| c.state) {
-        Some(GameState::Configuring) => {
-            let channels = data
-                .get(&ctx.guild_id().unwrap())
-                .unwrap()
-                .teams
-                .iter()
-                .map(|t| t.channel.clone())
-                .chain([data.get(&ctx.guild_id().unwrap()).unwrap().admin_channel])
-                .collect::<Vec<_>>();
-            if channels.len() == 1 {
-                utils::send_error(ctx, "No team has been added").await?;
-            } else {
-                {
-                    data.get_mut(&ctx.guild_id().unwrap()).unwrap().state = GameState::Started;
-                    broadcast_message(ctx, channels,
-
-                         create_embed((0,0,0), "New game !",
-r"We will soon start the blindtest !
-If you know the name of the song, and the author/band that made it, send a message **in lowercase** here
-One point will be given for the author/band name and one point for the song name
-Be careful about mistakes in the response, and remember: One message for the song name, and one for the band name
-
-ENJOY :D")).await?;
-                    utils::send_reply(ctx, {
-                        use std::fmt::Write;
-                        let mut s = String::new();
-                        writeln!(
-                            &mut s,
-                            "Here are the next answers for the question: `{}`\n",
-                            data.get_mut(&ctx.guild_id().unwrap())
-                                .unwrap()
-                                .questions
-                                .last()
-                                .unwrap()
-                                .name
-                        )?;
-                        for rep in data
-                            .get_mut(&ctx.guild_id().unwrap())
-                            .unwrap()
-                            .questions
-                            .last()
-                            .unwrap()
-                            .answer
-                            .iter()
-                        {
-                            match rep {
-                                config::Answer::SingleAnswer(srep) => {
-                                    writeln!(&mut s, "-> `{srep}`")?;
-                                }
-                                config::Answer::MutlipleAnswer(rep_alias, _) => {
-                                    if rep_alias.len() == 1 {
-                                        writeln!(&mut s, "-> `{}`", rep_alias[0])?;
-                                    } else {
-                                        let mut iter = rep_alias.iter().peekable();
-                                        if let Some(r) = iter.next() {
-                                            writeln!(&mut s, "┌ -> `{r}`")?;
-                                        }
-                                        while let Some(r) = iter.next() {
-                                            if iter.peek().is_none() {
-                                                writeln!(&mut s, "└ -> `{r}`")?;
-                                            } else {
-                                                writeln!(&mut s, "│ -> `{r}`")?;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        writeln!(
-                            &mut s,
-                            "\nThere are {} remaining questions",
-                            data.get_mut(&ctx.guild_id().unwrap())
-                                .unwrap()
-                                .questions
-                                .len()
-                                - 1
-                        )?;
-                        s
-                    })
-                    .await?;
-                }
+    let admin = ctx.author().id;
+    let started = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No Game exist in this guild");
+            };
+            if game.teams.is_empty() {
+                return Err("No team has been added");
             }
+            let effects = game.engine.handle(Input::Start, game.settings.rules())?;
+            apply(game, outbox, effects, Source::Admin(admin));
+            Ok(templates::render(
+                &game.settings,
+                Template::NextQuestion,
+                &templates::next_question_args(game),
+            ))
+        })
+        .await?;
+    match started {
+        Ok(msg) => utils::send_reply(ctx, msg).await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Skip the current question, even if some answers weren't found
+#[poise::command(slash_command, rename = "skip", guild_only)]
+pub async fn skip_question(ctx: Context<'_>) -> Result<(), Error> {
+    let admin = ctx.author().id;
+    let skipped = ctx
+        .data()
+        .games
+        .ask(ctx.guild_id().unwrap(), |reply| Command::Skip {
+            admin,
+            reply,
+        })
+        .await?;
+    match skipped {
+        Ok(()) => utils::send_reply(ctx, "Skipped the question").await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Stop accepting the guesses and the buzzes until the game is resumed
+#[poise::command(slash_command, rename = "pause", guild_only)]
+pub async fn pause_game(ctx: Context<'_>) -> Result<(), Error> {
+    set_paused(ctx, true).await
+}
+
+/// Accept the guesses and the buzzes again
+#[poise::command(slash_command, rename = "resume", guild_only)]
+pub async fn resume_game(ctx: Context<'_>) -> Result<(), Error> {
+    set_paused(ctx, false).await
+}
+
+async fn set_paused(ctx: Context<'_>, paused: bool) -> Result<(), Error> {
+    let admin = ctx.author().id;
+    let result = ctx
+        .data()
+        .games
+        .ask(ctx.guild_id().unwrap(), |reply| Command::Pause {
+            admin,
+            paused,
+            reply,
+        })
+        .await?;
+    match result {
+        Ok(()) if paused => utils::send_reply(ctx, "Paused the game").await,
+        Ok(()) => utils::send_reply(ctx, "Resumed the game").await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Export the event log of the game as a json file
+#[poise::command(slash_command, rename = "log", guild_only)]
+pub async fn export_log(
+    ctx: Context<'_>,
+    #[description = "id of a previous game, the current game by default"] game: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let (game_id, events) = match game {
+        Some(id) => {
+            let Ok(id) = id.parse::<u64>() else {
+                utils::send_error(ctx, "The game id should be a number").await?;
+                return Ok(());
+            };
+            (id, ctx.data().storage.events(guild_id, id)?)
         }
         None => {
-            utils::send_error(ctx, "No Game exist in this guild").await?;
-        }
-        _ => {
-            utils::send_error(ctx, "The game has already started, or it has ended").await?;
+            let Some(game) = ctx.data().games.get(guild_id).await? else {
+                utils::send_error(ctx, "No game exists").await?;
+                return Ok(());
+            };
+            (game.id, game.events)
         }
+    };
+    if events.is_empty() {
+        utils::send_error(ctx, "No event was recorded for this game").await?;
+        return Ok(());
     }
+    let json = serde_json::to_vec_pretty(&events)?;
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(create_embed(
+                &settings::current(ctx),
+                Tone::Success,
+                "Success!",
+                tr!(
+                    i18n::lang(ctx),
+                    "The game `{game}` has {events} events",
+                    game = game_id,
+                    events = events.len()
+                ),
+            ))
+            .attachment(serenity::all::CreateAttachment::bytes(json, "events.json")),
+    )
+    .await?;
     Ok(())
 }
 
+/// Points given to a team that joins a running game
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum LateStart {
+    /// Start from zero
+    #[default]
+    Zero,
+    /// Start with the average points of the other teams
+    Average,
+}
+
+/// Check that `channel` can be used by a team, telling the user why otherwise
+pub async fn is_text_channel(
+    ctx: Context<'_>,
+    channel: serenity::all::ChannelId,
+) -> Result<bool, Error> {
+    let Some(channel_info) = ctx.http().get_channel(channel).await?.guild() else {
+        utils::send_error(ctx, "The given channel isn't in the guild !").await?;
+        return Ok(false);
+    };
+    if !matches!(channel_info.kind, serenity::all::ChannelType::Text) {
+        utils::send_error(ctx, "The channel given isn't a text channel").await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 /// Add a team to the current game
 #[poise::command(slash_command, rename = "add", guild_only)]
 pub async fn add_team_game(
     ctx: Context<'_>,
     #[description = "team name"] name: String,
     #[description = "team discord channel"] channel: serenity::all::ChannelId,
+    #[description = "only the members of this role may answer for the team"] role: Option<
+        serenity::all::RoleId,
+    >,
+    #[description = "points of a team added while the game is running"] late_start: Option<
+        LateStart,
+    >,
 ) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-    if !data.contains_key(&ctx.guild_id().unwrap()) {
-        utils::send_error(ctx, "No game exists").await?;
+    if !is_text_channel(ctx, channel).await? {
         return Ok(());
     }
-    if data
-        .get(&ctx.guild_id().unwrap())
-        .map(|gconfig| gconfig.teams.iter().any(|e| e.channel == channel))
-        .unwrap_or_default()
-    {
-        utils::send_error(ctx, "A team already exists with that channel !").await?;
-        return Ok(());
-    }
-    if data
-        .get(&ctx.guild_id().unwrap())
-        .map(|gconfig| gconfig.teams.iter().any(|e| e.name == name))
-        .unwrap_or_default()
-    {
-        utils::send_error(ctx, "A team already exists with that name !").await?;
-        return Ok(());
-    }
-    let Some(channel_info) = ctx.http().get_channel(channel).await?.guild() else {
-        utils::send_error(ctx, "The given channel isn't in the guild !").await?;
-        return Ok(());
+    let team = config::Team {
+        name,
+        channel,
+        leaderboard: HashMap::new(),
+        total_points: 0.0,
+        role,
+        disqualified: None,
     };
-
-    if !matches!(channel_info.kind, serenity::all::ChannelType::Text) {
-        utils::send_error(ctx, "The channel given isn't a text channel").await?;
-        return Ok(());
+    let admin = ctx.author().id;
+    let added = ctx
+        .data()
+        .games
+        .ask(ctx.guild_id().unwrap(), |reply| Command::AddTeam {
+            team,
+            late_start: late_start.unwrap_or_default(),
+            admin,
+            reply,
+        })
+        .await?;
+    if let Err(e) = added {
+        return utils::send_error(ctx, e).await;
     }
-    data.get_mut(&ctx.guild_id().unwrap())
-        .unwrap()
-        .teams
-        .push(config::Team {
-            name,
-            channel,
-            leaderboard: HashMap::new(),
-            total_points: 0.0,
-        });
     utils::send_reply(
         ctx,
-        format!(
-            "Created a new team that will respond in {}",
-            channel.mention()
+        tr!(
+            i18n::lang(ctx),
+            "Created a new team that will respond in {channel}",
+            channel = channel.mention()
         ),
     )
     .await?;
@@ -335,32 +684,522 @@ pub async fn add_team_game(
     Ok(())
 }
 
+/// Add a team to the game, a team joining the game while it runs starting with the points
+/// of `late_start`
+pub fn add_team(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    team: config::Team,
+    late_start: LateStart,
+    admin: serenity::all::UserId,
+) -> Result<(), &'static str> {
+    if matches!(game.engine.state, GameState::Ended) {
+        return Err("The game is finished");
+    }
+    if game.teams.iter().any(|e| e.channel == team.channel) {
+        return Err("A team already exists with that channel !");
+    }
+    if game.teams.iter().any(|e| e.name == team.name) {
+        return Err("A team already exists with that name !");
+    }
+    if team.role.is_some() && game.teams.iter().any(|e| e.role == team.role) {
+        return Err("A team already exists with that role !");
+    }
+    let name = team.name.clone();
+    let channel = team.channel;
+    game.teams.push(team);
+    game.log(
+        GameEvent::TeamAdded {
+            team: name.clone(),
+            channel,
+        },
+        None,
+    );
+    if !game.engine.is_running() {
+        return Ok(());
+    }
+    let active: Vec<_> = game
+        .teams
+        .iter()
+        .filter(|t| t.name != name && t.disqualified.is_none())
+        .map(|t| t.total_points)
+        .collect();
+    let points = match late_start {
+        LateStart::Zero => 0.0,
+        LateStart::Average if active.is_empty() => 0.0,
+        LateStart::Average => active.iter().sum::<f64>() / active.len() as f64,
+    };
+    if points != 0.0 {
+        game.teams.last_mut().unwrap().total_points = points;
+        game.record_adjustment(
+            config::Adjustment {
+                admin,
+                team: name.clone(),
+                user: None,
+                amount: points,
+                reason: tr!(game.settings.language, "Joined the game late"),
+                at: serenity::all::Timestamp::now(),
+            },
+            None,
+        );
+    }
+    outbox.broadcast(
+        [channel],
+        create_embed(
+            &game.settings,
+            Tone::Success,
+            "Welcome !",
+            tr!(
+                game.settings.language,
+                "Team `{team}` joins the game at question {number} with {points}pts",
+                team = name,
+                number = game.engine.question_number,
+                points = points
+            ),
+        ),
+    );
+    Ok(())
+}
+
+/// Join a team of the current game
+#[poise::command(slash_command, rename = "join", guild_only, category = "Player")]
+pub async fn join_team(
+    ctx: Context<'_>,
+    #[description = "team name"]
+    #[autocomplete = "autocomplete_team"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let user = ctx.author().id;
+    let Some(game) = ctx.data().games.get(guild_id).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+    let Some(team) = game.teams.iter().find(|t| t.name == name) else {
+        utils::send_error(ctx, "No team exists with this name !").await?;
+        return Ok(());
+    };
+    if team.disqualified.is_some() {
+        utils::send_error(ctx, "This team has been disqualified").await?;
+        return Ok(());
+    }
+    let Some(role) = team.role else {
+        utils::send_error(ctx, "This team has no role, just answer in its channel").await?;
+        return Ok(());
+    };
+    let channel = team.channel;
+    let roles = guild_id.member(ctx, user).await?.roles;
+    if roles.contains(&role) {
+        utils::send_error(ctx, "You are already part of this team").await?;
+        return Ok(());
+    }
+    let mut joined = roles.clone();
+    joined.push(role);
+    if !game.may_answer(channel, user, &joined) {
+        utils::send_error(ctx, "You are already part of another team").await?;
+        return Ok(());
+    }
+    ctx.http()
+        .add_member_role(guild_id, user, role, Some("Joined a blindtest team"))
+        .await?;
+    let team = name.clone();
+    ctx.data()
+        .games
+        .edit(guild_id, move |game, _| {
+            if let Some(game) = game {
+                game.log(GameEvent::TeamJoined { team, user }, None);
+            }
+        })
+        .await?;
+    let msg = tr!(
+        game.settings.language,
+        "{user} joined team `{team}`, answer in {channel}",
+        user = user.mention(),
+        team = name,
+        channel = channel.mention()
+    );
+    utils::send_reply(ctx, msg).await
+}
+
 /// Remove a team from the current game
 #[poise::command(slash_command, rename = "remove", guild_only)]
 pub async fn remove_team_game(
     ctx: Context<'_>,
     #[description = "team name"] name: String,
 ) -> Result<(), Error> {
-    let mut data = ctx.data().games.write().await;
-    if !data.contains_key(&ctx.guild_id().unwrap()) {
-        utils::send_error(ctx, "No game exists").await?;
-        return Ok(());
+    let team = name.clone();
+    let removed = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, _| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            let Some(pos) = game.teams.iter().position(|e| e.name == team) else {
+                return Err("No team exists with this name !");
+            };
+            if game.engine.is_running() {
+                return Err("The game is running, use `/game teams disqualify` instead");
+            }
+            game.teams.remove(pos);
+            game.log(GameEvent::TeamRemoved { team }, None);
+            Ok(())
+        })
+        .await?;
+    if let Err(e) = removed {
+        return utils::send_error(ctx, e).await;
     }
-    if !data
-        .get(&ctx.guild_id().unwrap())
-        .map(|gconfig| gconfig.teams.iter().any(|e| e.name == name))
-        .unwrap_or_default()
+    let msg = tr!(i18n::lang(ctx), "Removed team named {team} !", team = name);
+    utils::send_reply(ctx, msg).await?;
+    Ok(())
+}
+
+/// What made the engine act, recorded in the game log
+#[derive(Clone, Copy)]
+pub enum Source<'a> {
+    Admin(serenity::all::UserId),
+    /// A guess of a player, along with how its content is shown to the other teams
+    Message(&'a serenity::all::Message, &'a str),
+    /// The time passing
+    Timer,
+}
+
+/// Record what the engine did in the game and queue the messages telling the teams
+pub fn apply(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    effects: Vec<Effect>,
+    source: Source<'_>,
+) {
+    let channels = game
+        .teams
+        .iter()
+        .map(|t| t.channel)
+        .chain([game.admin_channel])
+        .collect::<Vec<_>>();
+    for effect in effects {
+        match effect {
+            Effect::Started => {
+                game.log(GameEvent::GameStarted, None);
+                outbox.broadcast(
+                    channels.clone(),
+                    templates::embed(
+                        &game.settings,
+                        Template::Intro,
+                        &[
+                            ("teams", game.teams.len().to_string()),
+                            ("questions", game.engine.questions.len().to_string()),
+                        ],
+                    ),
+                );
+            }
+            Effect::QuestionOpened { number, question } => {
+                game.question_opened_at = serenity::all::Timestamp::now();
+                game.log(GameEvent::QuestionOpened { number, question }, None);
+                // The admin that started the game is told about the first question
+                if number > 1 {
+                    outbox.admin(templates::embed(
+                        &game.settings,
+                        Template::NextQuestion,
+                        &templates::next_question_args(game),
+                    ));
+                }
+                buzzer::open_question(outbox, game);
+            }
+            Effect::Found {
+                team,
+                player,
+                number,
+                question,
+                answer,
+                points,
+                remaining,
+            } => {
+                let Source::Message(message, shown) = source else {
+                    continue;
+                };
+                let channel = serenity::all::ChannelId::new(team);
+                let user = serenity::all::UserId::new(player);
+                let Some(team) = game.teams.iter_mut().find(|t| t.channel == channel) else {
+                    continue;
+                };
+                *team.leaderboard.entry(user).or_default() += points;
+                team.total_points += points;
+                let team_name = team.name.clone();
+                let total_points = team.total_points;
+                game.log(
+                    GameEvent::Match {
+                        number,
+                        team: team_name.clone(),
+                        user,
+                        answer: answer.clone(),
+                        points,
+                    },
+                    Some(message.id),
+                );
+                game.found.push(config::FoundAnswer {
+                    question_number: number,
+                    question,
+                    answer,
+                    team: team_name.clone(),
+                    user,
+                    points,
+                    at: message.timestamp,
+                    elapsed_ms: message.timestamp.timestamp_millis()
+                        - game.question_opened_at.timestamp_millis(),
+                });
+                outbox.broadcast(
+                    channels.clone(),
+                    templates::embed(
+                        &game.settings,
+                        Template::AnswerFound,
+                        &[
+                            ("user", user.mention().to_string()),
+                            ("team", team_name),
+                            ("answer", config::Question::normalize_string(shown)),
+                            ("gained", points.to_string()),
+                            ("points", total_points.to_string()),
+                        ],
+                    ),
+                );
+                if buzzer::accepts_guess(game, channel) {
+                    buzzer::answer_found(outbox, game, remaining);
+                }
+            }
+            Effect::Skipped { number, question } => {
+                if let Source::Admin(admin) = source {
+                    let event = GameEvent::Skip {
+                        number,
+                        question,
+                        admin,
+                    };
+                    game.log(event, None);
+                }
+            }
+            Effect::TimedOut { number, question } => {
+                game.log(
+                    GameEvent::TimedOut {
+                        number,
+                        question: question.clone(),
+                    },
+                    None,
+                );
+                outbox.broadcast(
+                    channels.clone(),
+                    create_embed(
+                        &game.settings,
+                        Tone::Warning,
+                        "Time's up !",
+                        tr!(
+                            game.settings.language,
+                            "The time to answer `{question}` is over",
+                            question = question
+                        ),
+                    ),
+                );
+            }
+            Effect::QuestionClosed {
+                question,
+                remaining,
+                ..
+            } => {
+                // The last question is followed by the end of the game instead
+                if remaining > 0 {
+                    outbox.broadcast(
+                        channels.clone(),
+                        templates::embed(
+                            &game.settings,
+                            Template::QuestionDone,
+                            &[("question", question), ("remaining", remaining.to_string())],
+                        ),
+                    );
+                }
+            }
+            Effect::Ended { finished } => {
+                game.log(GameEvent::GameEnded, None);
+                if finished {
+                    outbox.broadcast(
+                        channels.clone(),
+                        templates::embed(
+                            &game.settings,
+                            Template::GameOver,
+                            &[("questions", game.engine.question_number.to_string())],
+                        ),
+                    );
+                }
+                // Only games that were started have results
+                if game.engine.question_number > 0 {
+                    results::broadcast_results(outbox, game);
+                }
+            }
+            Effect::Paused => {
+                if let Source::Admin(admin) = source {
+                    game.log(GameEvent::Paused { admin }, None);
+                }
+                buzzer::pause(game);
+                outbox.broadcast(
+                    channels.clone(),
+                    create_embed(
+                        &game.settings,
+                        Tone::Warning,
+                        "Game paused",
+                        "The game is paused, wait for it to resume before answering",
+                    ),
+                );
+            }
+            Effect::Resumed => {
+                if let Source::Admin(admin) = source {
+                    game.log(GameEvent::Resumed { admin }, None);
+                }
+                outbox.broadcast(
+                    channels.clone(),
+                    create_embed(
+                        &game.settings,
+                        Tone::Info,
+                        "Game resumed",
+                        "The game is back, you can answer again !",
+                    ),
+                );
+                buzzer::resume(outbox, game);
+            }
+        }
+    }
+}
+
+/// Count the guess of `message` as `answer`, recording it as an adjustment made by `admin`
+pub fn accept(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    message: &serenity::all::Message,
+    answer: &str,
+    admin: serenity::all::UserId,
+) -> Result<(), String> {
+    let lang = game.settings.language;
+    let Some(team) = game.teams.iter().find(|t| t.channel == message.channel_id) else {
+        return Err(String::from("The message wasn't sent in a team channel"));
+    };
+    let team = team.name.clone();
+    let input = Input::Accept {
+        team: message.channel_id.get(),
+        player: message.author.id.get(),
+        answer: answer.to_owned(),
+    };
+    let effects = game
+        .engine
+        .handle(input, game.settings.rules())
+        .map_err(String::from)?;
+    let Some(pts) = effects.iter().find_map(|e| match e {
+        Effect::Found { points, .. } => Some(*points),
+        _ => None,
+    }) else {
+        return Err(tr!(
+            lang,
+            "`{answer}` isn't a remaining answer of the current question",
+            answer = answer
+        ));
+    };
+    apply(game, outbox, effects, Source::Message(message, answer));
+    game.record_adjustment(
+        config::Adjustment {
+            admin,
+            team,
+            user: Some(message.author.id),
+            amount: pts,
+            reason: tr!(
+                lang,
+                "accepted `{guess}` as `{answer}`",
+                guess = message.content,
+                answer = answer
+            ),
+            at: serenity::all::Timestamp::now(),
+        },
+        Some(message.id),
+    );
+    Ok(())
+}
+
+/// Check a message sent by a player against the answers, returning whether the game must
+/// be saved. Every guess checked is logged, so the game is saved even for a wrong one
+pub fn guess(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    message: &serenity::all::Message,
+    shown: &str,
+    roles: &[serenity::all::RoleId],
+) -> bool {
+    let channel_id = message.channel_id;
+    let author = message.author.id;
+    if !game.engine.is_running()
+        || game.engine.paused
+        || !game.teams.iter().any(|t| t.channel == channel_id)
     {
-        utils::send_error(ctx, "No team exists with this name !").await?;
-        return Ok(());
+        return false;
     }
-    let gconfig = data
-        .get_mut(&ctx.guild_id().ok_or("Not in a guild ???")?)
-        .unwrap();
-    let Some(pos) = gconfig.teams.iter().position(|e| e.name == name) else {
-        return Ok(());
+    if !buzzer::accepts_guess(game, channel_id) {
+        return false;
+    }
+    if !game.may_answer(channel_id, author, roles) {
+        outbox.react(message, '⛔');
+        return false;
+    }
+    let team = game.team_name(channel_id);
+    // The team of a player is kept to stop them from guessing for another one
+    game.guessed_in
+        .entry(author)
+        .or_insert_with(|| team.clone());
+    game.log(
+        GameEvent::Guess {
+            team,
+            user: author,
+            content: message.content.clone(),
+        },
+        Some(message.id),
+    );
+    let input = Input::Guess {
+        team: channel_id.get(),
+        player: author.get(),
+        text: message.content.clone(),
     };
-    gconfig.teams.swap_remove(pos);
-    utils::send_reply(ctx, format!("Removed team named {name} !")).await?;
+    let Ok(effects) = game.engine.handle(input, game.settings.rules()) else {
+        return true;
+    };
+    // A wrong guess makes the team holding the buzzer lose it
+    if !effects.iter().any(|e| matches!(e, Effect::Found { .. })) {
+        buzzer::release(outbox, game, "gave a wrong answer");
+    }
+    apply(game, outbox, effects, Source::Message(message, shown));
+    true
+}
+
+/// Close the current question, even if some answers weren't found
+pub fn skip(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    admin: serenity::all::UserId,
+) -> Result<(), &'static str> {
+    let effects = game.engine.handle(Input::Skip, game.settings.rules())?;
+    apply(game, outbox, effects, Source::Admin(admin));
+    Ok(())
+}
+
+/// Stop or resume accepting the guesses and the buzzes. The team holding the buzzer keeps
+/// it, with the time it had left when the game was paused
+pub fn pause(
+    game: &mut config::GuildConfig,
+    outbox: &mut Outbox,
+    admin: serenity::all::UserId,
+    paused: bool,
+) -> Result<(), &'static str> {
+    let input = if paused { Input::Pause } else { Input::Resume };
+    let effects = game.engine.handle(input, game.settings.rules())?;
+    apply(game, outbox, effects, Source::Admin(admin));
     Ok(())
 }
+
+/// Let the time limit of the current question run out
+pub fn tick(game: &mut config::GuildConfig, outbox: &mut Outbox, seconds: u64) {
+    let input = Input::Tick { seconds };
+    if let Ok(effects) = game.engine.handle(input, game.settings.rules()) {
+        apply(game, outbox, effects, Source::Timer);
+    }
+}
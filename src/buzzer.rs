@@ -0,0 +1,476 @@
+use serenity::all::{
+    ButtonStyle, ChannelId, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+    CreateMessage, Mentionable, Timestamp,
+};
+
+use crate::config::{BuzzHolder, BuzzPress, GuildConfig};
+use crate::events::GameEvent;
+use crate::i18n;
+use crate::settings::Tone;
+use crate::utils::{create_embed, respond, Outbox};
+use crate::{tr, Data, Error};
+
+/// Custom id of the button sent to the teams
+pub const BUZZ_BUTTON: &str = "blindtest-buzz";
+
+fn buzz_row() -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(BUZZ_BUTTON)
+        .label("Buzz")
+        .emoji('🔔')
+        .style(ButtonStyle::Danger)])
+}
+
+/// Queue the buzz button for every team that is still allowed to buzz
+fn send_buzzer(outbox: &mut Outbox, game: &GuildConfig, msg: CreateEmbed) {
+    let Some(buzzer) = &game.buzzer else {
+        return;
+    };
+    let channels = game
+        .teams
+        .iter()
+        .filter(|t| !buzzer.locked_out.contains(&t.channel) && t.disqualified.is_none())
+        .map(|t| t.channel);
+    outbox.push(
+        channels,
+        vec![CreateMessage::new().embed(msg).components(vec![buzz_row()])],
+    );
+}
+
+/// Whether a message sent in `channel` should be checked against the answers
+pub fn accepts_guess(game: &GuildConfig, channel: ChannelId) -> bool {
+    match &game.buzzer {
+        None => true,
+        Some(buzzer) => buzzer.holder.as_ref().is_some_and(|h| h.channel == channel),
+    }
+}
+
+/// Reset the buzzer for the current question and send the buzz button to the teams
+pub fn open_question(outbox: &mut Outbox, game: &mut GuildConfig) {
+    let Some(buzzer) = game.buzzer.as_mut() else {
+        return;
+    };
+    buzzer.reset();
+    send_buzzer(
+        outbox,
+        game,
+        create_embed(
+            &game.settings,
+            Tone::Warning,
+            "Buzzer open !",
+            "Press the button to buzz, the first team to buzz gets to answer",
+        ),
+    );
+}
+
+/// Stop the time of the team holding the buzzer while the game is paused
+pub fn pause(game: &mut GuildConfig) {
+    if let Some(buzzer) = game.buzzer.as_mut() {
+        buzzer.paused_at = Some(Timestamp::now());
+    }
+}
+
+/// Give the team holding the buzzer the rest of its time once the game resumes, or send
+/// the buzz button again if nobody holds it
+pub fn resume(outbox: &mut Outbox, game: &mut GuildConfig) {
+    let Some(buzzer) = game.buzzer.as_mut() else {
+        return;
+    };
+    let paused_at = buzzer.paused_at.take();
+    if let Some(holder) = buzzer.holder.as_mut() {
+        if let Some(paused_at) = paused_at {
+            let paused = Timestamp::now().unix_timestamp() - paused_at.unix_timestamp();
+            holder.paused_secs += paused.max(0) as u64;
+        }
+        return;
+    }
+    send_buzzer(
+        outbox,
+        game,
+        create_embed(
+            &game.settings,
+            Tone::Warning,
+            "Buzzer open !",
+            "Press the button to buzz, the first team to buzz gets to answer",
+        ),
+    );
+}
+
+/// Take the buzzer away from the team holding it and reopen it for the other teams
+pub fn release(outbox: &mut Outbox, game: &mut GuildConfig, reason: &str) {
+    let Some(buzzer) = game.buzzer.as_mut() else {
+        return;
+    };
+    let Some(holder) = buzzer.holder.take() else {
+        return;
+    };
+    buzzer.locked_out.push(holder.channel);
+    if game
+        .teams
+        .iter()
+        .filter(|t| t.disqualified.is_none())
+        .all(|t| buzzer.locked_out.contains(&t.channel))
+    {
+        buzzer.locked_out.clear();
+    }
+    let name = game.team_name(holder.channel);
+    let msg = create_embed(
+        &game.settings,
+        Tone::Warning,
+        "Buzzer open !",
+        tr!(
+            game.settings.language,
+            "Team `{team}` {reason}, the buzzer is open again !",
+            team = name,
+            reason = i18n::translate(game.settings.language, reason)
+        ),
+    );
+    outbox.broadcast([holder.channel, game.admin_channel], msg.clone());
+    send_buzzer(outbox, game, msg);
+}
+
+/// Reopen the buzzer for every team once the team holding it found an answer, if
+/// `remaining` answers are still to be found
+pub fn answer_found(outbox: &mut Outbox, game: &mut GuildConfig, remaining: usize) {
+    let Some(buzzer) = game.buzzer.as_mut() else {
+        return;
+    };
+    buzzer.holder = None;
+    buzzer.locked_out.clear();
+    if remaining > 0 {
+        send_buzzer(
+            outbox,
+            game,
+            create_embed(
+                &game.settings,
+                Tone::Warning,
+                "Buzzer open !",
+                "There are still answers to find, buzz again !",
+            ),
+        );
+    }
+}
+
+/// Take the buzzer away from the team holding it once its time is up
+pub fn tick(game: &mut GuildConfig, outbox: &mut Outbox) {
+    if game.engine.paused || !game.engine.is_running() {
+        return;
+    }
+    let Some(buzzer) = &game.buzzer else {
+        return;
+    };
+    let Some(holder) = &buzzer.holder else {
+        return;
+    };
+    let press = &buzzer.presses[holder.press];
+    let deadline = press.at.unix_timestamp() + buzzer.window as i64 + holder.paused_secs as i64;
+    if Timestamp::now().unix_timestamp() >= deadline {
+        release(outbox, game, "ran out of time");
+    }
+}
+
+/// Give the buzzer to the team pressing it if it's free, returning the answer to the
+/// press or `None` if the buzzer isn't active in its channel
+pub fn press(
+    game: &mut GuildConfig,
+    outbox: &mut Outbox,
+    press: BuzzPress,
+    locale: &str,
+) -> Option<(CreateEmbed, bool)> {
+    let channel_id = press.channel;
+    let user = press.user;
+    if !game.engine.is_running()
+        || game.buzzer.is_none()
+        || !game
+            .teams
+            .iter()
+            .any(|t| t.channel == channel_id && t.disqualified.is_none())
+    {
+        return None;
+    }
+    let user_settings = game.settings.for_locale(locale);
+    if game.engine.paused {
+        let msg = create_embed(&user_settings, Tone::Error, "Error!", "The game is paused");
+        return Some((msg, true));
+    }
+    let name = game.team_name(channel_id);
+    let question = game
+        .engine
+        .current()
+        .map(|q| q.name.clone())
+        .unwrap_or_default();
+    game.log(
+        GameEvent::Buzz {
+            team: name.clone(),
+            user,
+        },
+        None,
+    );
+    let buzzer = game.buzzer.as_mut().unwrap();
+    buzzer.presses.push(press);
+    let press = buzzer.presses.len() - 1;
+    let elapsed = buzzer.elapsed_ms(&buzzer.presses[press]);
+    let locked_out = buzzer.locked_out.contains(&channel_id);
+    let holder = buzzer.holder.as_ref().map(|h| h.channel);
+    let window = buzzer.window;
+
+    let (reply, outcome) = if locked_out {
+        let msg = create_embed(
+            &user_settings,
+            Tone::Error,
+            "Locked out",
+            "Your team already lost the buzzer for this answer",
+        );
+        ((msg, true), "locked out")
+    } else if let Some(holder) = holder {
+        let msg = create_embed(
+            &user_settings,
+            Tone::Error,
+            "Too late !",
+            tr!(
+                user_settings.language,
+                "Team `{team}` was faster",
+                team = game.team_name(holder)
+            ),
+        );
+        ((msg, true), "too late")
+    } else {
+        game.buzzer.as_mut().unwrap().holder = Some(BuzzHolder {
+            channel: channel_id,
+            press,
+            paused_secs: 0,
+        });
+        let msg = create_embed(
+            &game.settings,
+            Tone::Warning,
+            "Buzz !",
+            tr!(
+                game.settings.language,
+                "{user} buzzed for team `{team}` !\nYou have {window} seconds to answer",
+                user = user.mention(),
+                team = name,
+                window = window
+            ),
+        );
+        outbox.broadcast(
+            game.teams
+                .iter()
+                .map(|t| t.channel)
+                .filter(|c| *c != channel_id)
+                .collect::<Vec<_>>(),
+            create_embed(
+                &game.settings,
+                Tone::Warning,
+                "Buzz !",
+                tr!(
+                    game.settings.language,
+                    "Team `{team}` has the buzzer for {window} seconds",
+                    team = name,
+                    window = window
+                ),
+            ),
+        );
+        ((msg, false), "got the buzzer")
+    };
+    outbox.admin(create_embed(
+        &game.settings,
+        Tone::Info,
+        "Buzz",
+        tr!(
+            game.settings.language,
+            "Buzz #{number} on `{question}`: team `{team}` ({user}) at +{elapsed} ms, {outcome}",
+            number = press + 1,
+            question = question,
+            team = name,
+            user = user.mention(),
+            elapsed = elapsed,
+            outcome = i18n::translate(game.settings.language, outcome)
+        ),
+    ));
+    Some(reply)
+}
+
+/// Handle a press on the buzz button
+pub async fn handle_interaction(
+    ctx: &serenity::all::Context,
+    interaction: &ComponentInteraction,
+    data: &Data,
+) -> Result<(), Error> {
+    if interaction.data.custom_id != BUZZ_BUTTON {
+        return Ok(());
+    }
+    let Some(guild_id) = interaction.guild_id else {
+        return Ok(());
+    };
+    let buzz = BuzzPress {
+        channel: interaction.channel_id,
+        user: interaction.user.id,
+        at: interaction.id.created_at(),
+    };
+    let locale = interaction.locale.clone();
+    let reply = data
+        .games
+        .edit(guild_id, move |game, outbox| {
+            game.as_mut()
+                .and_then(|game| press(game, outbox, buzz, &locale))
+        })
+        .await?;
+    let (msg, ephemeral) = match reply {
+        Some(reply) => reply,
+        None => {
+            let settings = data
+                .storage
+                .settings(guild_id)?
+                .for_locale(&interaction.locale);
+            let msg = create_embed(
+                &settings,
+                Tone::Error,
+                "Error!",
+                "The buzzer isn't active here",
+            );
+            (msg, true)
+        }
+    };
+    respond(ctx, interaction, msg, ephemeral).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::UserId;
+
+    use super::*;
+    use crate::config::{prepare_questions, Answer, Question, Team};
+    use crate::engine::{Input, Rules};
+    use crate::settings::GuildSettings;
+
+    const WINDOW: u64 = 5;
+
+    fn team(i: u64) -> ChannelId {
+        ChannelId::new(i)
+    }
+
+    fn game(teams: u64) -> GuildConfig {
+        let settings = GuildSettings {
+            buzzer_window: Some(WINDOW),
+            ..GuildSettings::default()
+        };
+        let question = Question {
+            name: String::from("q"),
+            answer: vec![Answer::SingleAnswer(String::from("a"))],
+            ..Default::default()
+        };
+        let mut game = GuildConfig::new(1, team(99), prepare_questions(vec![question]), settings);
+        game.engine.handle(Input::Start, Rules::default()).unwrap();
+        game.teams = (1..=teams)
+            .map(|t| Team {
+                name: format!("team {t}"),
+                leaderboard: Default::default(),
+                total_points: 0.0,
+                channel: team(t),
+                role: None,
+                disqualified: None,
+            })
+            .collect();
+        game
+    }
+
+    /// Press the buzzer of a team `ago` seconds ago, telling whether the reply is only
+    /// shown to the player
+    fn buzz(game: &mut GuildConfig, channel: ChannelId, ago: i64) -> Option<bool> {
+        let at = Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() - ago).unwrap();
+        let buzz = BuzzPress {
+            channel,
+            user: UserId::new(channel.get()),
+            at,
+        };
+        let mut outbox = Outbox::new(game);
+        press(game, &mut outbox, buzz, "en-US").map(|(_, ephemeral)| ephemeral)
+    }
+
+    fn tick_now(game: &mut GuildConfig) {
+        let mut outbox = Outbox::new(game);
+        tick(game, &mut outbox);
+    }
+
+    fn release_now(game: &mut GuildConfig) {
+        let mut outbox = Outbox::new(game);
+        release(&mut outbox, game, "ran out of time");
+    }
+
+    fn holder(game: &GuildConfig) -> Option<ChannelId> {
+        game.buzzer.as_ref()?.holder.as_ref().map(|h| h.channel)
+    }
+
+    #[test]
+    fn first_team_to_buzz_gets_to_answer() {
+        let mut game = game(2);
+        assert_eq!(buzz(&mut game, team(1), 0), Some(false));
+        assert_eq!(buzz(&mut game, team(2), 0), Some(true));
+        assert_eq!(holder(&game), Some(team(1)));
+        assert!(accepts_guess(&game, team(1)));
+        assert!(!accepts_guess(&game, team(2)));
+        // Channels without a team have no buzzer
+        assert_eq!(buzz(&mut game, team(3), 0), None);
+    }
+
+    #[test]
+    fn buzzer_is_taken_back_once_its_time_is_up() {
+        let mut answering = game(2);
+        buzz(&mut answering, team(1), WINDOW as i64 - 2);
+        tick_now(&mut answering);
+        assert_eq!(holder(&answering), Some(team(1)));
+
+        let mut game = game(2);
+        buzz(&mut game, team(1), WINDOW as i64 + 1);
+        // The time doesn't run while the game is paused
+        game.engine.paused = true;
+        tick_now(&mut game);
+        assert_eq!(holder(&game), Some(team(1)));
+        game.engine.paused = false;
+        tick_now(&mut game);
+        assert_eq!(holder(&game), None);
+        assert_eq!(game.buzzer.as_ref().unwrap().locked_out, [team(1)]);
+        // The team that lost the buzzer can't take it back for this answer
+        assert_eq!(buzz(&mut game, team(1), 0), Some(true));
+        assert_eq!(buzz(&mut game, team(2), 0), Some(false));
+    }
+
+    #[test]
+    fn time_paused_isnt_taken_from_the_window() {
+        let mut game = game(2);
+        buzz(&mut game, team(1), WINDOW as i64 - 2);
+        game.engine.paused = true;
+        pause(&mut game);
+        // The game stays paused for longer than the window
+        let earlier = |at: Timestamp, secs: i64| {
+            Timestamp::from_unix_timestamp(at.unix_timestamp() - secs).unwrap()
+        };
+        let buzzer = game.buzzer.as_mut().unwrap();
+        buzzer.paused_at = buzzer.paused_at.map(|at| earlier(at, 2 * WINDOW as i64));
+        buzzer.presses[0].at = earlier(buzzer.presses[0].at, 2 * WINDOW as i64);
+        game.engine.paused = false;
+        let mut outbox = Outbox::new(&game);
+        resume(&mut outbox, &mut game);
+        // The others don't get the buzz button while a team holds the buzzer
+        assert!(outbox.is_empty());
+        tick_now(&mut game);
+        assert_eq!(holder(&game), Some(team(1)));
+
+        // The 2 seconds left when the game was paused still run out
+        let press = &mut game.buzzer.as_mut().unwrap().presses[0];
+        press.at = earlier(press.at, 2);
+        tick_now(&mut game);
+        assert_eq!(holder(&game), None);
+    }
+
+    #[test]
+    fn release_reopens_the_buzzer_once_every_team_lost_it() {
+        let mut game = game(2);
+        buzz(&mut game, team(1), 0);
+        release_now(&mut game);
+        assert_eq!(game.buzzer.as_ref().unwrap().locked_out, [team(1)]);
+        buzz(&mut game, team(2), 0);
+        release_now(&mut game);
+        assert!(game.buzzer.as_ref().unwrap().locked_out.is_empty());
+        assert_eq!(buzz(&mut game, team(1), 0), Some(false));
+    }
+}
@@ -2,68 +2,278 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-pub enum GameState {
-    Configuring,
-    Started,
-    Ended,
-}
+pub use crate::engine::{prepare_questions, Answer, GameState, Question};
+use crate::events::{GameEvent, LoggedEvent};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum Answer {
-    SingleAnswer(String),
-    MutlipleAnswer(Vec<String>, #[serde(skip)] usize),
+pub struct GuildConfig {
+    /// Unique id of the game, taken from the command that created it
+    #[serde(default)]
+    pub id: u64,
+    pub teams: Vec<Team>,
+    pub admin_channel: serenity::all::ChannelId,
+    /// Questions left and progress of the game
+    #[serde(flatten)]
+    pub engine: crate::engine::Engine,
+    #[serde(default)]
+    pub buzzer: Option<Buzzer>,
+    /// Manual changes made to the scores by the admins
+    #[serde(default)]
+    pub adjustments: Vec<Adjustment>,
+    /// Every answer found during the game, in order
+    #[serde(default)]
+    pub found: Vec<FoundAnswer>,
+    #[serde(default)]
+    pub question_opened_at: serenity::all::Timestamp,
+    /// Everything that happened during the game, in order. The storage keeps it apart from
+    /// the game so that saving a game doesn't write its whole log again
+    #[serde(skip)]
+    pub events: Vec<LoggedEvent>,
+    /// Channels and roles created by the bot for the teams
+    #[serde(default)]
+    pub provisioned: Provisioned,
+    /// Players that pressed the signup button, in order
+    #[serde(default)]
+    pub signups: Vec<serenity::all::UserId>,
+    /// Team each player sent their first guess in, the only one they can answer for
+    #[serde(default)]
+    pub guessed_in: HashMap<serenity::all::UserId, String>,
+    /// Settings of the guild when the game was created
+    #[serde(default)]
+    pub settings: crate::settings::GuildSettings,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Question {
-    pub name: String,
-    pub answer: Vec<Answer>,
+/// Discord resources created by `/game teams create`, deleted by `/game teams cleanup`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Provisioned {
+    pub category: Option<serenity::all::ChannelId>,
+    pub channels: Vec<serenity::all::ChannelId>,
+    pub roles: Vec<serenity::all::RoleId>,
 }
 
-impl Question {
-    pub fn normalize_string(s: &str) -> String {
-        s.split_whitespace()
-            .collect::<Vec<_>>()
-            .join(" ")
-            .to_ascii_lowercase()
+impl GuildConfig {
+    /// A game that wasn't started yet, played in buzzer mode if the settings say so
+    pub fn new(
+        id: u64,
+        admin_channel: serenity::all::ChannelId,
+        questions: Vec<Question>,
+        settings: crate::settings::GuildSettings,
+    ) -> Self {
+        Self {
+            id,
+            teams: Vec::new(),
+            admin_channel,
+            engine: crate::engine::Engine::new(questions),
+            buzzer: settings.buzzer_window.map(Buzzer::new),
+            adjustments: Vec::new(),
+            found: Vec::new(),
+            question_opened_at: serenity::all::Timestamp::now(),
+            events: Vec::new(),
+            provisioned: Provisioned::default(),
+            signups: Vec::new(),
+            guessed_in: HashMap::new(),
+            settings,
+        }
     }
 
-    pub fn is_answer(&self, s: &str) -> bool {
-        self.get_answer_pos(s).is_some()
+    /// Append an event to the log of the game
+    pub fn log(&mut self, event: GameEvent, message: Option<serenity::all::MessageId>) {
+        self.events.push(LoggedEvent {
+            at: serenity::all::Timestamp::now(),
+            message,
+            event,
+        });
     }
 
-    pub fn get_answer_pos(&self, s: &str) -> Option<(usize, f64)> {
-        let words = Self::normalize_string(s);
-        let pos = self.answer.iter().position(|a| match a {
-            Answer::SingleAnswer(astr) => *astr == words,
-            Answer::MutlipleAnswer(astrs, _) => astrs.iter().any(|s| *s == words),
-        });
-        pos.map(|p| {
-            (
-                p,
-                match self.answer.get(p).unwrap() {
-                    Answer::SingleAnswer(_) => 1.0,
-                    Answer::MutlipleAnswer(astrs, len) => {
-                        if astrs.len() == *len {
-                            1.0
-                        } else {
-                            0.5
-                        }
-                    }
-                },
-            )
+    /// Record a manual change of the points made by an admin
+    pub fn record_adjustment(
+        &mut self,
+        adjustment: Adjustment,
+        message: Option<serenity::all::MessageId>,
+    ) {
+        self.log(
+            GameEvent::Adjustment {
+                admin: adjustment.admin,
+                team: adjustment.team.clone(),
+                user: adjustment.user,
+                amount: adjustment.amount,
+                reason: adjustment.reason.clone(),
+            },
+            message,
+        );
+        self.adjustments.push(adjustment);
+    }
+
+    /// Whether `user`, who has `roles`, may answer in the channel of a team: a player can only
+    /// be part of one team, the one they first guessed in, and teams backed by a role only
+    /// accept its members
+    pub fn may_answer(
+        &self,
+        channel: serenity::all::ChannelId,
+        user: serenity::all::UserId,
+        roles: &[serenity::all::RoleId],
+    ) -> bool {
+        let Some(team) = self.teams.iter().find(|t| t.channel == channel) else {
+            return false;
+        };
+        if team.disqualified.is_some() {
+            return false;
+        }
+        if team.role.is_some_and(|r| !roles.contains(&r)) {
+            return false;
+        }
+        !self.teams.iter().any(|t| {
+            t.channel != channel
+                && (t.leaderboard.contains_key(&user)
+                    || self.guessed_in.get(&user) == Some(&t.name)
+                    || t.role.is_some_and(|r| roles.contains(&r)))
         })
     }
+
+    /// Name of the team answering in `channel`
+    pub fn team_name(&self, channel: serenity::all::ChannelId) -> String {
+        self.teams
+            .iter()
+            .find(|t| t.channel == channel)
+            .map(|t| t.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Rename a team, including in the answers and adjustments already recorded
+    pub fn rename_team(&mut self, name: &str, new_name: &str) {
+        for team in self.teams.iter_mut().filter(|t| t.name == name) {
+            team.name = new_name.to_owned();
+        }
+        for found in self.found.iter_mut().filter(|f| f.team == name) {
+            found.team = new_name.to_owned();
+        }
+        for adj in self.adjustments.iter_mut().filter(|a| a.team == name) {
+            adj.team = new_name.to_owned();
+        }
+        for team in self.guessed_in.values_mut().filter(|t| *t == name) {
+            *team = new_name.to_owned();
+        }
+        self.log(
+            GameEvent::TeamRenamed {
+                team: name.to_owned(),
+                name: new_name.to_owned(),
+            },
+            None,
+        );
+    }
+
+    /// Make a team answer in `channel`, keeping its buzzer state
+    pub fn move_team(&mut self, name: &str, channel: serenity::all::ChannelId) {
+        let Some(team) = self.teams.iter_mut().find(|t| t.name == name) else {
+            return;
+        };
+        let old = std::mem::replace(&mut team.channel, channel);
+        if let Some(buzzer) = &mut self.buzzer {
+            let holder = buzzer.holder.iter_mut().map(|h| &mut h.channel);
+            let presses = buzzer.presses.iter_mut().map(|p| &mut p.channel);
+            for c in holder
+                .chain(presses)
+                .chain(buzzer.locked_out.iter_mut())
+                .filter(|c| **c == old)
+            {
+                *c = channel;
+            }
+        }
+        self.log(
+            GameEvent::TeamMoved {
+                team: name.to_owned(),
+                channel,
+            },
+            None,
+        );
+    }
 }
 
+/// An answer found by a player
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct GuildConfig {
-    pub teams: Vec<Team>,
-    pub admin_channel: serenity::all::ChannelId,
-    pub state: GameState,
-    pub questions: Vec<Question>,
+pub struct FoundAnswer {
+    pub question_number: usize,
+    pub question: String,
+    pub answer: String,
+    pub team: String,
+    pub user: serenity::all::UserId,
+    pub points: f64,
+    pub at: serenity::all::Timestamp,
+    /// Milliseconds between the question being opened and the answer, negative when an
+    /// admin accepted a message sent before the question was opened
+    pub elapsed_ms: i64,
+}
+
+/// A manual change of the points made by an admin
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Adjustment {
+    pub admin: serenity::all::UserId,
+    pub team: String,
+    pub user: Option<serenity::all::UserId>,
+    pub amount: f64,
+    pub reason: String,
+    pub at: serenity::all::Timestamp,
+}
+
+/// State of the buzzer when the game is played in buzzer mode
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Buzzer {
+    /// Number of seconds the team that buzzed has to give an answer
+    pub window: u64,
+    /// Team currently allowed to answer, if any
+    pub holder: Option<BuzzHolder>,
+    /// Teams that already lost the buzzer for the current answer
+    pub locked_out: Vec<serenity::all::ChannelId>,
+    /// Every buzz pressed since the question was opened, in order
+    pub presses: Vec<BuzzPress>,
+    pub opened_at: serenity::all::Timestamp,
+    /// When the game was paused, if it is
+    #[serde(default)]
+    pub paused_at: Option<serenity::all::Timestamp>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuzzHolder {
+    pub channel: serenity::all::ChannelId,
+    /// Index of the winning press in `Buzzer::presses`
+    pub press: usize,
+    /// Seconds the game was paused while the team held the buzzer, they don't count in
+    /// its window
+    #[serde(default)]
+    pub paused_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuzzPress {
+    pub channel: serenity::all::ChannelId,
+    pub user: serenity::all::UserId,
+    pub at: serenity::all::Timestamp,
+}
+
+impl Buzzer {
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            holder: None,
+            locked_out: Vec::new(),
+            presses: Vec::new(),
+            opened_at: serenity::all::Timestamp::now(),
+            paused_at: None,
+        }
+    }
+
+    /// Reset the buzzer for a freshly opened question
+    pub fn reset(&mut self) {
+        self.holder = None;
+        self.locked_out.clear();
+        self.presses.clear();
+        self.opened_at = serenity::all::Timestamp::now();
+    }
+
+    /// Milliseconds elapsed between the question opening and the press
+    pub fn elapsed_ms(&self, press: &BuzzPress) -> i64 {
+        press.at.timestamp_millis() - self.opened_at.timestamp_millis()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -72,4 +282,10 @@ pub struct Team {
     pub leaderboard: HashMap<serenity::all::UserId, f64>,
     pub total_points: f64,
     pub channel: serenity::all::ChannelId,
+    /// Only the members of this role may answer for the team
+    #[serde(default)]
+    pub role: Option<serenity::all::RoleId>,
+    /// Why the team was disqualified, it can't answer anymore but stays in the results
+    #[serde(default)]
+    pub disqualified: Option<String>,
 }
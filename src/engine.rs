@@ -0,0 +1,815 @@
+//! Rules of a game, independent from Discord: the questions, how guesses are matched and
+//! scored and how the game moves from one question to the next. The commands feed it
+//! [`Input`]s and turn the [`Effect`]s it returns into messages.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum GameState {
+    Configuring,
+    Started,
+    Ended,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Answer {
+    SingleAnswer(String),
+    MutlipleAnswer(Vec<String>, #[serde(skip)] usize),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Question {
+    pub name: String,
+    pub answer: Vec<Answer>,
+    /// Audio file the host plays for the question, relative to the pack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    /// Release year of the song, for the host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+    /// Clue the host can give when the teams are stuck
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Seconds the teams have to find the answers before the question is closed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<u64>,
+}
+
+impl Question {
+    pub fn normalize_string(s: &str) -> String {
+        s.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_ascii_lowercase()
+    }
+
+    /// Find the answer matching `s` with at most `tolerance` typos, returning its position,
+    /// the points it is worth and the name that matched
+    pub fn get_answer_pos(&self, s: &str, tolerance: usize) -> Option<(usize, f64, String)> {
+        let words = Self::normalize_string(s);
+        let names = self.answer.iter().enumerate().flat_map(|(p, a)| match a {
+            Answer::SingleAnswer(astr) => either::Left(std::iter::once((p, astr))),
+            Answer::MutlipleAnswer(astrs, _) => either::Right(astrs.iter().map(move |a| (p, a))),
+        });
+        // A typo is only forgiven every four letters, so short answers must be exact
+        let (pos, name) = names
+            .map(|(p, a)| (p, a, typos(a, &words)))
+            .filter(|(_, a, d)| *d <= tolerance.min(a.chars().count() / 4))
+            .min_by_key(|(_, _, d)| *d)
+            .map(|(p, a, _)| (p, a.clone()))?;
+        let points = match &self.answer[pos] {
+            Answer::SingleAnswer(_) => 1.0,
+            Answer::MutlipleAnswer(astrs, len) => {
+                if astrs.len() == *len {
+                    1.0
+                } else {
+                    0.5
+                }
+            }
+        };
+        Some((pos, points, name))
+    }
+}
+
+/// Levenshtein distance between two strings
+fn typos(lhs: &str, rhs: &str) -> usize {
+    let rhs: Vec<char> = rhs.chars().collect();
+    let mut row: Vec<usize> = (0..=rhs.len()).collect();
+    for (i, l) in lhs.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, r) in rhs.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if l == *r {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+    row[rhs.len()]
+}
+
+/// Directory of the quiz packs, the ones of a guild being in a directory named after its id
+pub const QUIZ_DIR: &str = "blind_test";
+/// Quiz pack played when a game is created without one
+pub const DEFAULT_PACK: &str = "blind_test/music.json";
+
+/// Questions of the quiz pack at `path`, as written in the file
+pub fn read_pack(
+    path: &std::path::Path,
+) -> Result<Vec<Question>, Box<dyn std::error::Error + Send + Sync>> {
+    let file = std::fs::File::open(path)?;
+    Ok(serde_json::from_reader(&file)?)
+}
+
+/// Read the quiz pack at `path` and get it ready to be played
+pub fn load_questions(
+    path: &std::path::Path,
+) -> Result<Vec<Question>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(prepare_questions(read_pack(path)?))
+}
+
+/// Get a quiz pack ready to be played: answers are normalized and the questions are
+/// reversed so that the next one is always the last
+pub fn prepare_questions(mut questions: Vec<Question>) -> Vec<Question> {
+    questions
+        .iter_mut()
+        .flat_map(|s| s.answer.iter_mut())
+        .flat_map(|s| match s {
+            Answer::MutlipleAnswer(s, len) => {
+                *len = s.len();
+                either::Left(s.iter_mut())
+            }
+            Answer::SingleAnswer(s) => either::Right(std::iter::once(s)),
+        })
+        .for_each(|s| {
+            let taken = std::mem::take(s);
+            *s = Question::normalize_string(&taken);
+        });
+    questions.reverse();
+    questions
+}
+
+/// The quiz pack format doesn't keep the number of answers of an alias group, which the
+/// scoring needs when a game is saved and restored
+mod played_questions {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Answer, Question};
+
+    #[derive(Serialize)]
+    struct Out<'a> {
+        #[serde(flatten)]
+        question: &'a Question,
+        totals: Vec<usize>,
+    }
+
+    #[derive(Deserialize)]
+    struct In {
+        #[serde(flatten)]
+        question: Question,
+        #[serde(default)]
+        totals: Vec<usize>,
+    }
+
+    pub fn serialize<S: Serializer>(questions: &[Question], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(questions.iter().map(|question| {
+            Out {
+                question,
+                totals: question
+                    .answer
+                    .iter()
+                    .map(|a| match a {
+                        Answer::SingleAnswer(_) => 1,
+                        Answer::MutlipleAnswer(_, len) => *len,
+                    })
+                    .collect(),
+            }
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Question>, D::Error> {
+        let questions = Vec::<In>::deserialize(d)?;
+        Ok(questions
+            .into_iter()
+            .map(|mut q| {
+                for (answer, total) in q.question.answer.iter_mut().zip(q.totals) {
+                    if let Answer::MutlipleAnswer(_, len) = answer {
+                        *len = total;
+                    }
+                }
+                q.question
+            })
+            .collect())
+    }
+}
+
+/// Id of a team, the engine doesn't care what it refers to
+pub type TeamId = u64;
+/// Id of a player
+pub type PlayerId = u64;
+
+/// How guesses are matched and scored
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rules {
+    /// Typos forgiven in a guess, only one every four letters of the answer
+    pub tolerance: usize,
+    /// Every answer is worth one point, even the other names of an answer already found
+    pub flat: bool,
+    /// Seconds given to answer the questions without their own time limit
+    pub time_limit: Option<u64>,
+}
+
+/// Something happening to a game
+#[derive(Clone, Debug)]
+pub enum Input {
+    Start,
+    /// A player of `team` sent `text`
+    Guess {
+        team: TeamId,
+        player: PlayerId,
+        text: String,
+    },
+    /// An admin counts a guess of `player` as `answer`, even while the game is paused
+    Accept {
+        team: TeamId,
+        player: PlayerId,
+        answer: String,
+    },
+    /// Close the current question, even if some answers weren't found
+    Skip,
+    /// `seconds` went by, closing the current question once its time limit is reached
+    Tick {
+        seconds: u64,
+    },
+    Pause,
+    Resume,
+    /// End the game, even if there are remaining questions
+    End,
+}
+
+/// What a game did in answer to an [`Input`], in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    Started,
+    QuestionOpened {
+        number: usize,
+        question: String,
+    },
+    Found {
+        team: TeamId,
+        player: PlayerId,
+        number: usize,
+        question: String,
+        answer: String,
+        points: f64,
+        /// Answers of the question that are still to be found
+        remaining: usize,
+    },
+    Skipped {
+        number: usize,
+        question: String,
+    },
+    /// The time limit of the question was reached before every answer was found
+    TimedOut {
+        number: usize,
+        question: String,
+    },
+    QuestionClosed {
+        number: usize,
+        question: String,
+        /// Questions left after this one
+        remaining: usize,
+    },
+    /// The game ended, `finished` when every question was played
+    Ended {
+        finished: bool,
+    },
+    Paused,
+    Resumed,
+}
+
+/// State of a game: the questions left to play, the last one being the current question
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Engine {
+    pub state: GameState,
+    #[serde(with = "played_questions")]
+    pub questions: Vec<Question>,
+    /// Number of the current question, starting at 1
+    #[serde(default)]
+    pub question_number: usize,
+    /// Guesses are ignored while the game is paused
+    #[serde(default)]
+    pub paused: bool,
+    /// Seconds left to answer the current question, if it has a time limit
+    #[serde(default)]
+    pub time_left: Option<u64>,
+}
+
+impl Engine {
+    /// A game being configured, playing `questions` as returned by [`prepare_questions`]
+    pub fn new(questions: Vec<Question>) -> Self {
+        Self {
+            state: GameState::Configuring,
+            questions,
+            question_number: 0,
+            paused: false,
+            time_left: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, GameState::Started)
+    }
+
+    /// The question being played
+    pub fn current(&self) -> Option<&Question> {
+        self.questions.last()
+    }
+
+    /// Apply `input` to the game, or tell why it can't be
+    pub fn handle(&mut self, input: Input, rules: Rules) -> Result<Vec<Effect>, &'static str> {
+        let mut effects = Vec::new();
+        match input {
+            Input::Start => {
+                if !matches!(self.state, GameState::Configuring) {
+                    return Err("The game has already started, or it has ended");
+                }
+                if self.questions.is_empty() {
+                    return Err("The questions list is empty");
+                }
+                self.state = GameState::Started;
+                effects.push(Effect::Started);
+                self.open_question(rules, &mut effects);
+            }
+            Input::Guess { team, player, text } => {
+                if !self.is_running() {
+                    return Err("No game is running");
+                }
+                if self.paused {
+                    return Err("The game is paused");
+                }
+                if self.questions.is_empty() {
+                    self.state = GameState::Ended;
+                    effects.push(Effect::Ended { finished: false });
+                    return Ok(effects);
+                }
+                self.find(team, player, &text, rules, &mut effects);
+            }
+            Input::Accept {
+                team,
+                player,
+                answer,
+            } => {
+                if !self.is_running() {
+                    return Err("No game is running");
+                }
+                self.find(team, player, &answer, rules, &mut effects);
+            }
+            Input::Skip => {
+                if !self.is_running() {
+                    return Err("No game is running");
+                }
+                let Some(question) = self.questions.last_mut() else {
+                    return Err("There is no question left");
+                };
+                question.answer.clear();
+                effects.push(Effect::Skipped {
+                    number: self.question_number,
+                    question: question.name.clone(),
+                });
+                self.close_question(rules, &mut effects);
+            }
+            Input::Tick { seconds } => {
+                // The time doesn't run while the game is paused
+                if !self.is_running() || self.paused {
+                    return Ok(effects);
+                }
+                let Some(left) = self.time_left.as_mut() else {
+                    return Ok(effects);
+                };
+                *left = left.saturating_sub(seconds);
+                if *left > 0 {
+                    return Ok(effects);
+                }
+                if let Some(question) = self.questions.last_mut() {
+                    question.answer.clear();
+                    effects.push(Effect::TimedOut {
+                        number: self.question_number,
+                        question: question.name.clone(),
+                    });
+                }
+                self.close_question(rules, &mut effects);
+            }
+            Input::Pause | Input::Resume => {
+                let paused = matches!(input, Input::Pause);
+                if !self.is_running() {
+                    return Err("No game is running");
+                }
+                if self.paused == paused {
+                    return Err(if paused {
+                        "The game is already paused"
+                    } else {
+                        "The game isn't paused"
+                    });
+                }
+                self.paused = paused;
+                effects.push(if paused {
+                    Effect::Paused
+                } else {
+                    Effect::Resumed
+                });
+            }
+            Input::End => {
+                if matches!(self.state, GameState::Ended) {
+                    return Err("The game was already finished");
+                }
+                self.state = GameState::Ended;
+                effects.push(Effect::Ended { finished: false });
+            }
+        }
+        Ok(effects)
+    }
+
+    fn open_question(&mut self, rules: Rules, effects: &mut Vec<Effect>) {
+        self.question_number += 1;
+        self.time_left = self
+            .current()
+            .and_then(|q| q.time_limit.or(rules.time_limit));
+        if let Some(question) = self.questions.last() {
+            effects.push(Effect::QuestionOpened {
+                number: self.question_number,
+                question: question.name.clone(),
+            });
+        }
+    }
+
+    /// Credit `team` with the remaining answer of the current question matching `guess`
+    fn find(
+        &mut self,
+        team: TeamId,
+        player: PlayerId,
+        guess: &str,
+        rules: Rules,
+        effects: &mut Vec<Effect>,
+    ) {
+        let Some(question) = self.questions.last_mut() else {
+            return;
+        };
+        let Some((pos, points, answer)) = question.get_answer_pos(guess, rules.tolerance) else {
+            return;
+        };
+        let remove = match &mut question.answer[pos] {
+            Answer::MutlipleAnswer(names, _) => {
+                names.retain(|s| *s != answer);
+                names.is_empty()
+            }
+            Answer::SingleAnswer(_) => true,
+        };
+        if remove {
+            question.answer.swap_remove(pos);
+        }
+        effects.push(Effect::Found {
+            team,
+            player,
+            number: self.question_number,
+            question: question.name.clone(),
+            answer,
+            points: if rules.flat { 1.0 } else { points },
+            remaining: question.answer.len(),
+        });
+        if question.answer.is_empty() {
+            self.close_question(rules, effects);
+        }
+    }
+
+    /// Move on to the next question, ending the game after the last one
+    fn close_question(&mut self, rules: Rules, effects: &mut Vec<Effect>) {
+        let question = self.questions.pop().map(|q| q.name).unwrap_or_default();
+        effects.push(Effect::QuestionClosed {
+            number: self.question_number,
+            question,
+            remaining: self.questions.len(),
+        });
+        if self.questions.is_empty() {
+            self.state = GameState::Ended;
+            self.time_left = None;
+            effects.push(Effect::Ended { finished: true });
+        } else {
+            self.open_question(rules, effects);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEAM: TeamId = 1;
+    const OTHER: TeamId = 2;
+    const PLAYER: PlayerId = 10;
+
+    fn single(name: &str, answers: &[&str]) -> Question {
+        Question {
+            name: name.to_owned(),
+            answer: answers
+                .iter()
+                .map(|a| Answer::SingleAnswer((*a).to_owned()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn aliases(name: &str, names: &[&str]) -> Question {
+        Question {
+            name: name.to_owned(),
+            answer: vec![Answer::MutlipleAnswer(
+                names.iter().map(|a| (*a).to_owned()).collect(),
+                0,
+            )],
+            ..Default::default()
+        }
+    }
+
+    fn engine(questions: Vec<Question>) -> Engine {
+        Engine::new(prepare_questions(questions))
+    }
+
+    fn started(questions: Vec<Question>) -> Engine {
+        let mut engine = engine(questions);
+        engine.handle(Input::Start, Rules::default()).unwrap();
+        engine
+    }
+
+    fn guess(engine: &mut Engine, text: &str) -> Result<Vec<Effect>, &'static str> {
+        guess_with(engine, text, Rules::default())
+    }
+
+    fn guess_with(
+        engine: &mut Engine,
+        text: &str,
+        rules: Rules,
+    ) -> Result<Vec<Effect>, &'static str> {
+        engine.handle(
+            Input::Guess {
+                team: TEAM,
+                player: PLAYER,
+                text: text.to_owned(),
+            },
+            rules,
+        )
+    }
+
+    fn points(effects: &[Effect]) -> Vec<f64> {
+        effects
+            .iter()
+            .filter_map(|e| match e {
+                Effect::Found { points, .. } => Some(*points),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_spaces_and_case() {
+        assert_eq!(Question::normalize_string("  Daft   PUNK "), "daft punk");
+    }
+
+    #[test]
+    fn prepared_questions_are_played_in_order() {
+        let engine = engine(vec![single("first", &["a"]), single("second", &["B  c"])]);
+        assert_eq!(engine.current().unwrap().name, "first");
+        let Answer::SingleAnswer(answer) = &engine.questions[0].answer[0] else {
+            panic!("expected a single answer");
+        };
+        assert_eq!(answer, "b c");
+    }
+
+    #[test]
+    fn typos_are_forgiven_on_long_answers_only() {
+        let question = single("q", &["bohemian rhapsody", "abba"]);
+        assert!(question.get_answer_pos("bohemian rapsody", 0).is_none());
+        assert!(question.get_answer_pos("bohemian rapsody", 1).is_some());
+        assert!(question.get_answer_pos("bohemain rapsody", 1).is_none());
+        assert!(question.get_answer_pos("abdd", 2).is_none());
+        assert_eq!(question.get_answer_pos("ABBA", 0).unwrap().2, "abba");
+    }
+
+    #[test]
+    fn start_opens_the_first_question() {
+        let mut engine = engine(vec![single("first", &["a"]), single("second", &["b"])]);
+        let effects = engine.handle(Input::Start, Rules::default()).unwrap();
+        assert_eq!(
+            effects,
+            [
+                Effect::Started,
+                Effect::QuestionOpened {
+                    number: 1,
+                    question: "first".to_owned()
+                }
+            ]
+        );
+        assert!(engine.is_running());
+        assert!(engine.handle(Input::Start, Rules::default()).is_err());
+    }
+
+    #[test]
+    fn empty_game_cant_start() {
+        let mut engine = engine(Vec::new());
+        assert!(engine.handle(Input::Start, Rules::default()).is_err());
+        assert!(matches!(engine.state, GameState::Configuring));
+    }
+
+    #[test]
+    fn guesses_need_a_running_game() {
+        let mut engine = engine(vec![single("q", &["a"])]);
+        assert!(guess(&mut engine, "a").is_err());
+        engine.handle(Input::End, Rules::default()).unwrap();
+        assert!(guess(&mut engine, "a").is_err());
+    }
+
+    #[test]
+    fn wrong_guess_changes_nothing() {
+        let mut engine = started(vec![single("q", &["a", "b"])]);
+        assert!(guess(&mut engine, "c").unwrap().is_empty());
+        assert_eq!(engine.current().unwrap().answer.len(), 2);
+    }
+
+    #[test]
+    fn found_answer_is_credited_and_removed() {
+        let mut engine = started(vec![single("q", &["a", "b"])]);
+        let effects = guess(&mut engine, "A").unwrap();
+        assert_eq!(
+            effects,
+            [Effect::Found {
+                team: TEAM,
+                player: PLAYER,
+                number: 1,
+                question: "q".to_owned(),
+                answer: "a".to_owned(),
+                points: 1.0,
+                remaining: 1,
+            }]
+        );
+        assert!(guess(&mut engine, "a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn other_names_of_an_answer_are_worth_half() {
+        let mut engine = started(vec![aliases("q", &["a", "b", "c"]), single("next", &["d"])]);
+        assert_eq!(points(&guess(&mut engine, "b").unwrap()), [1.0]);
+        assert_eq!(points(&guess(&mut engine, "a").unwrap()), [0.5]);
+        assert_eq!(points(&guess(&mut engine, "b").unwrap()), Vec::<f64>::new());
+        assert_eq!(points(&guess(&mut engine, "c").unwrap()), [0.5]);
+        assert_eq!(engine.current().unwrap().name, "next");
+    }
+
+    #[test]
+    fn flat_scoring_gives_one_point_per_answer() {
+        let rules = Rules {
+            flat: true,
+            ..Default::default()
+        };
+        let mut engine = started(vec![aliases("q", &["a", "b"]), single("next", &["c"])]);
+        assert_eq!(points(&guess_with(&mut engine, "a", rules).unwrap()), [1.0]);
+        assert_eq!(points(&guess_with(&mut engine, "b", rules).unwrap()), [1.0]);
+    }
+
+    #[test]
+    fn tolerance_comes_from_the_rules() {
+        let rules = Rules {
+            tolerance: 1,
+            ..Default::default()
+        };
+        let mut engine = started(vec![single("q", &["bohemian rhapsody", "x"])]);
+        assert!(guess(&mut engine, "bohemian rapsody").unwrap().is_empty());
+        let effects = guess_with(&mut engine, "bohemian rapsody", rules).unwrap();
+        let Some(Effect::Found { answer, .. }) = effects.first() else {
+            panic!("expected an answer to be found");
+        };
+        assert_eq!(answer, "bohemian rhapsody");
+    }
+
+    #[test]
+    fn last_answer_moves_on_to_the_next_question() {
+        let mut engine = started(vec![single("first", &["a"]), single("second", &["b"])]);
+        let effects = guess(&mut engine, "a").unwrap();
+        assert_eq!(
+            effects[1..],
+            [
+                Effect::QuestionClosed {
+                    number: 1,
+                    question: "first".to_owned(),
+                    remaining: 1
+                },
+                Effect::QuestionOpened {
+                    number: 2,
+                    question: "second".to_owned()
+                }
+            ]
+        );
+        assert_eq!(engine.question_number, 2);
+    }
+
+    #[test]
+    fn last_question_ends_the_game() {
+        let mut engine = started(vec![single("q", &["a"])]);
+        let effects = guess(&mut engine, "a").unwrap();
+        assert_eq!(effects.last(), Some(&Effect::Ended { finished: true }));
+        assert!(matches!(engine.state, GameState::Ended));
+        assert!(engine.questions.is_empty());
+    }
+
+    #[test]
+    fn skip_closes_the_question() {
+        let mut engine = started(vec![single("first", &["a"]), single("second", &["b"])]);
+        let effects = engine.handle(Input::Skip, Rules::default()).unwrap();
+        assert_eq!(
+            effects[0],
+            Effect::Skipped {
+                number: 1,
+                question: "first".to_owned()
+            }
+        );
+        assert!(matches!(effects[1], Effect::QuestionClosed { .. }));
+        assert_eq!(engine.current().unwrap().name, "second");
+        engine.handle(Input::Skip, Rules::default()).unwrap();
+        assert!(matches!(engine.state, GameState::Ended));
+        assert!(engine.handle(Input::Skip, Rules::default()).is_err());
+    }
+
+    #[test]
+    fn paused_game_ignores_guesses_but_accepts_admin_answers() {
+        let mut engine = started(vec![single("q", &["a", "b"])]);
+        assert_eq!(
+            engine.handle(Input::Pause, Rules::default()),
+            Ok(vec![Effect::Paused])
+        );
+        assert!(engine.handle(Input::Pause, Rules::default()).is_err());
+        assert!(guess(&mut engine, "a").is_err());
+        let accept = Input::Accept {
+            team: OTHER,
+            player: PLAYER,
+            answer: "a".to_owned(),
+        };
+        let effects = engine.handle(accept, Rules::default()).unwrap();
+        assert!(matches!(effects[0], Effect::Found { team: OTHER, .. }));
+        assert_eq!(
+            engine.handle(Input::Resume, Rules::default()),
+            Ok(vec![Effect::Resumed])
+        );
+        assert!(engine.handle(Input::Resume, Rules::default()).is_err());
+        assert_eq!(points(&guess(&mut engine, "b").unwrap()), [1.0]);
+    }
+
+    fn tick(engine: &mut Engine, seconds: u64) -> Vec<Effect> {
+        engine
+            .handle(Input::Tick { seconds }, Rules::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn time_limit_closes_the_question() {
+        let mut timed = single("first", &["a", "b"]);
+        timed.time_limit = Some(30);
+        let mut engine = started(vec![timed, single("second", &["c"])]);
+        assert_eq!(engine.time_left, Some(30));
+        assert!(tick(&mut engine, 20).is_empty());
+        // The time doesn't run while the game is paused
+        engine.handle(Input::Pause, Rules::default()).unwrap();
+        assert!(tick(&mut engine, 20).is_empty());
+        engine.handle(Input::Resume, Rules::default()).unwrap();
+        let effects = tick(&mut engine, 10);
+        assert_eq!(
+            effects[0],
+            Effect::TimedOut {
+                number: 1,
+                question: "first".to_owned()
+            }
+        );
+        assert!(matches!(effects[1], Effect::QuestionClosed { .. }));
+        assert_eq!(engine.current().unwrap().name, "second");
+        // Questions without a time limit wait for their answers
+        assert_eq!(engine.time_left, None);
+        assert!(tick(&mut engine, 1000).is_empty());
+    }
+
+    #[test]
+    fn questions_without_a_time_limit_take_the_one_of_the_rules() {
+        let rules = Rules {
+            time_limit: Some(60),
+            ..Rules::default()
+        };
+        let mut timed = single("first", &["a"]);
+        timed.time_limit = Some(30);
+        let mut engine = engine(vec![timed, single("second", &["b"])]);
+        engine.handle(Input::Start, rules).unwrap();
+        assert_eq!(engine.time_left, Some(30));
+        guess_with(&mut engine, "a", rules).unwrap();
+        assert_eq!(engine.time_left, Some(60));
+    }
+
+    #[test]
+    fn end_stops_the_game_once() {
+        let mut engine = started(vec![single("q", &["a"])]);
+        assert_eq!(
+            engine.handle(Input::End, Rules::default()),
+            Ok(vec![Effect::Ended { finished: false }])
+        );
+        assert!(engine.handle(Input::End, Rules::default()).is_err());
+        assert!(engine.handle(Input::Start, Rules::default()).is_err());
+    }
+
+    #[test]
+    fn saved_game_keeps_the_scoring_of_aliases() {
+        let mut engine = started(vec![aliases("q", &["a", "b"]), single("next", &["c"])]);
+        guess(&mut engine, "a").unwrap();
+        let json = serde_json::to_string(&engine).unwrap();
+        let mut restored: Engine = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.question_number, 1);
+        assert_eq!(points(&guess(&mut restored, "b").unwrap()), [0.5]);
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, MessageId, Timestamp, UserId};
+
+/// Something that happened during a game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    GameCreated {
+        admin: UserId,
+        admin_channel: ChannelId,
+        questions: usize,
+    },
+    TeamAdded {
+        team: String,
+        channel: ChannelId,
+    },
+    TeamRemoved {
+        team: String,
+    },
+    TeamJoined {
+        team: String,
+        user: UserId,
+    },
+    TeamDisqualified {
+        team: String,
+        reason: String,
+    },
+    TeamRenamed {
+        team: String,
+        name: String,
+    },
+    TeamMoved {
+        team: String,
+        channel: ChannelId,
+    },
+    GameStarted,
+    QuestionOpened {
+        number: usize,
+        question: String,
+    },
+    Buzz {
+        team: String,
+        user: UserId,
+    },
+    Guess {
+        team: String,
+        user: UserId,
+        content: String,
+    },
+    Match {
+        number: usize,
+        team: String,
+        user: UserId,
+        answer: String,
+        points: f64,
+    },
+    Skip {
+        number: usize,
+        question: String,
+        admin: UserId,
+    },
+    /// The time limit of the question was reached
+    TimedOut {
+        number: usize,
+        question: String,
+    },
+    /// A manual change of the points, made at the time of the logged event. Logs written
+    /// before had the time twice, they are read back with the last one
+    Adjustment {
+        admin: UserId,
+        team: String,
+        user: Option<UserId>,
+        amount: f64,
+        reason: String,
+    },
+    Paused {
+        admin: UserId,
+    },
+    Resumed {
+        admin: UserId,
+    },
+    GameEnded,
+}
+
+/// An entry of the event log of a game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    pub at: Timestamp,
+    /// Discord message that triggered the event, if any
+    pub message: Option<MessageId>,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
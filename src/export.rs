@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use serenity::all::{CacheHttp, CreateAttachment, GuildId, UserId};
+
+use crate::config::GuildConfig;
+use crate::i18n;
+use crate::results::{player_standings, team_standings};
+use crate::Error;
+
+#[derive(Serialize)]
+pub struct TeamRow {
+    pub rank: usize,
+    pub team: String,
+    pub points: f64,
+    pub answers: usize,
+}
+
+#[derive(Serialize)]
+pub struct PlayerRow {
+    pub rank: usize,
+    pub player: String,
+    pub team: String,
+    pub points: f64,
+    pub answers: usize,
+}
+
+#[derive(Serialize)]
+pub struct QuestionRow {
+    pub number: usize,
+    pub question: String,
+    pub answer: String,
+    pub team: String,
+    pub player: String,
+    pub points: f64,
+    pub seconds: f64,
+}
+
+/// Results of a game, with the players referred to by their display name
+#[derive(Serialize)]
+pub struct Results {
+    pub teams: Vec<TeamRow>,
+    pub players: Vec<PlayerRow>,
+    pub questions: Vec<QuestionRow>,
+}
+
+/// Most members Discord sends at once
+const MEMBERS_PAGE: u64 = 1000;
+
+/// Display name of every player of the game, taken from the cache when it has the guild
+/// and otherwise fetched a page of members at a time. Players that left the guild are named
+/// after their account if the cache knows it
+pub async fn display_names(
+    ctx: impl CacheHttp,
+    guild_id: GuildId,
+    game: &GuildConfig,
+) -> HashMap<UserId, String> {
+    let players: HashSet<UserId> = game
+        .teams
+        .iter()
+        .flat_map(|t| t.leaderboard.keys().copied())
+        .collect();
+    let mut names = HashMap::new();
+    if let Some(guild) = ctx.cache().and_then(|c| c.guild(guild_id)) {
+        for user in &players {
+            if let Some(member) = guild.members.get(user) {
+                names.insert(*user, member.display_name().to_owned());
+            }
+        }
+    }
+    let mut after = None;
+    while names.len() < players.len() {
+        let Ok(members) = guild_id
+            .members(ctx.http(), Some(MEMBERS_PAGE), after)
+            .await
+        else {
+            break;
+        };
+        for member in &members {
+            if players.contains(&member.user.id) {
+                names.insert(member.user.id, member.display_name().to_owned());
+            }
+        }
+        match members.last() {
+            Some(last) if members.len() as u64 == MEMBERS_PAGE => after = Some(last.user.id),
+            _ => break,
+        }
+    }
+    for user in players {
+        names.entry(user).or_insert_with(|| {
+            ctx.cache()
+                .and_then(|c| c.user(user))
+                .map_or_else(|| unknown(game), |u| u.name.clone())
+        });
+    }
+    names
+}
+
+/// Name of a player that couldn't be found
+fn unknown(game: &GuildConfig) -> String {
+    i18n::translate(game.settings.language, "unknown player").to_owned()
+}
+
+impl Results {
+    pub fn new(game: &GuildConfig, names: &HashMap<UserId, String>) -> Self {
+        let name = |user: &UserId| names.get(user).cloned().unwrap_or_else(|| unknown(game));
+        let tie_breaker = game.settings.tie_breaker;
+        Self {
+            teams: team_standings(game, tie_breaker)
+                .into_iter()
+                .map(|s| TeamRow {
+                    rank: s.rank,
+                    team: s.entry.name.clone(),
+                    points: s.points,
+                    answers: s.answers,
+                })
+                .collect(),
+            players: player_standings(game, tie_breaker)
+                .into_iter()
+                .map(|s| PlayerRow {
+                    rank: s.rank,
+                    player: name(&s.entry.0),
+                    team: s.entry.1.name.clone(),
+                    points: s.points,
+                    answers: s.answers,
+                })
+                .collect(),
+            questions: game
+                .found
+                .iter()
+                .map(|f| QuestionRow {
+                    number: f.question_number,
+                    question: f.question.clone(),
+                    answer: f.answer.clone(),
+                    team: f.team.clone(),
+                    player: name(&f.user),
+                    points: f.points,
+                    seconds: f.elapsed_ms as f64 / 1000.0,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn json(&self) -> Result<CreateAttachment, Error> {
+        Ok(CreateAttachment::bytes(
+            serde_json::to_vec_pretty(self)?,
+            "results.json",
+        ))
+    }
+
+    pub fn csv(&self) -> Result<Vec<CreateAttachment>, Error> {
+        Ok(vec![
+            CreateAttachment::bytes(to_csv(&self.teams)?, "teams.csv"),
+            CreateAttachment::bytes(to_csv(&self.players)?, "players.csv"),
+            CreateAttachment::bytes(to_csv(&self.questions)?, "questions.csv"),
+        ])
+    }
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<Vec<u8>, Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
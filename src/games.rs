@@ -0,0 +1,692 @@
+//! The games of the guilds. A guild whose game is being played has a task owning its game
+//! and handling the commands sent to it in order. The other guilds have no task: their
+//! game, if any, is read from the storage when a command needs it.
+//!
+//! The guesses, the ticks and the commands of the hosts waiting on the game (skip, pause,
+//! adding a team) have their own [`Command`]. Every other command of the hosts changes the
+//! game with [`Games::edit`], or looks at it with [`Games::get`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use poise::BoxFuture;
+use serenity::all::{GuildId, Message, RoleId, UserId};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::blindtest::{self, LateStart};
+use crate::buzzer;
+use crate::config::{GameState, GuildConfig, Team};
+use crate::storage::Storage;
+use crate::utils::Outbox;
+use crate::Error;
+
+/// How often the timers of the games are checked
+const TICK: Duration = Duration::from_secs(1);
+
+/// Change made to the game of a guild, `None` when the guild has no game. Returns whether
+/// the game changed and must be saved
+pub type Edit = Box<dyn FnOnce(&mut Option<GuildConfig>, &mut Outbox) -> bool + Send>;
+/// Look at the game of a guild without changing it
+pub type Peek = Box<dyn FnOnce(Option<&GuildConfig>) + Send>;
+
+/// What the game of a guild can be asked to do, handled one after the other
+pub enum Command {
+    /// A message sent in the guild, checked against the answers if it comes from a team
+    Guess {
+        message: Box<Message>,
+        /// Content of the message with the mentions resolved, shown to the teams
+        shown: String,
+        /// Roles of the author
+        roles: Vec<RoleId>,
+    },
+    /// Close the current question, even if some answers weren't found
+    Skip {
+        admin: UserId,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    /// Stop or resume accepting the guesses and the buzzes
+    Pause {
+        admin: UserId,
+        paused: bool,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    /// Add a team to the game, running or not
+    AddTeam {
+        team: Team,
+        late_start: LateStart,
+        admin: UserId,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    /// Let the timers of the game run out, sent every second
+    Tick,
+    /// Any other change to the game
+    Edit(Edit),
+    /// Look at the game without changing it
+    Peek(Peek),
+}
+
+/// What a change made with [`Games::edit`] returns, telling whether the game must be saved
+pub trait Outcome {
+    fn changed(&self) -> bool;
+}
+
+impl Outcome for () {
+    fn changed(&self) -> bool {
+        true
+    }
+}
+
+impl Outcome for bool {
+    fn changed(&self) -> bool {
+        *self
+    }
+}
+
+impl<T> Outcome for Option<T> {
+    fn changed(&self) -> bool {
+        self.is_some()
+    }
+}
+
+impl<T, E> Outcome for Result<T, E> {
+    fn changed(&self) -> bool {
+        self.is_ok()
+    }
+}
+
+/// Does what a game queued on Discord
+pub type Dispatch = Arc<dyn Fn(Outbox) -> BoxFuture<'static, ()> + Send + Sync>;
+
+type Actors = Arc<Mutex<HashMap<GuildId, mpsc::UnboundedSender<Command>>>>;
+
+/// Games of every guild, each game being played owned by a task handling its commands in
+/// order so that a guild waiting on Discord doesn't hold up the others
+pub struct Games {
+    jobs: mpsc::UnboundedSender<Job>,
+    dispatch: Dispatch,
+    actors: Actors,
+    /// Held while a command is handled for a guild without a task
+    idle: tokio::sync::Mutex<()>,
+}
+
+/// Whether a game needs a task: it may still receive guesses or have timers running
+fn is_live(game: &Option<GuildConfig>) -> bool {
+    game.as_ref()
+        .is_some_and(|g| !matches!(g.engine.state, GameState::Ended))
+}
+
+impl Games {
+    /// Start the games saved in `storage` that weren't ended
+    pub fn new(storage: Arc<dyn Storage>, dispatch: Dispatch) -> Result<Self, Error> {
+        let saved = storage.games()?;
+        let games = Self {
+            jobs: worker(storage),
+            dispatch,
+            actors: Default::default(),
+            idle: Default::default(),
+        };
+        {
+            let mut actors = games.actors.lock().unwrap();
+            for (guild, game) in saved.into_iter().map(|(g, game)| (g, Some(game))) {
+                if is_live(&game) {
+                    actors.insert(guild, games.spawn(guild, game));
+                }
+            }
+        }
+        Ok(games)
+    }
+
+    fn spawn(&self, guild: GuildId, game: Option<GuildConfig>) -> mpsc::UnboundedSender<Command> {
+        let (sender, commands) = mpsc::unbounded_channel();
+        tokio::spawn(run(
+            guild,
+            game,
+            commands,
+            self.actors.clone(),
+            self.jobs.clone(),
+            self.dispatch.clone(),
+        ));
+        sender
+    }
+
+    /// Whether the game of `guild` is being played, the other guilds ignore guesses
+    pub fn is_live(&self, guild: GuildId) -> bool {
+        self.actors.lock().unwrap().contains_key(&guild)
+    }
+
+    /// Queue a command for the game of `guild`. Guilds whose game isn't being played ignore
+    /// it, use [`Games::ask`] for the commands that must be handled anyway
+    pub fn send(&self, guild: GuildId, command: Command) {
+        if let Some(actor) = self.actors.lock().unwrap().get(&guild) {
+            let _ = actor.send(command);
+        }
+    }
+
+    /// Send a command and wait for its answer. Without a task for the guild, the command is
+    /// handled on its stored game, and a game that is now being played gets a task
+    pub async fn ask<T>(
+        &self,
+        guild: GuildId,
+        command: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T, Error> {
+        let (reply, answer) = oneshot::channel();
+        let command = command(reply);
+        if let Some(command) = self.route(guild, command) {
+            let _idle = self.idle.lock().await;
+            // Another command may have started the game while waiting
+            if let Some(command) = self.route(guild, command) {
+                self.handle_idle(guild, command).await?;
+            }
+        }
+        Ok(answer
+            .await
+            .map_err(|_| "The game stopped before answering")?)
+    }
+
+    /// Give a command to the task of the guild, returning it if there is none
+    fn route(&self, guild: GuildId, command: Command) -> Option<Command> {
+        match self.actors.lock().unwrap().get(&guild) {
+            Some(actor) => actor.send(command).err().map(|e| e.0),
+            None => Some(command),
+        }
+    }
+
+    /// Handle a command on the stored game of a guild without a task
+    async fn handle_idle(&self, guild: GuildId, command: Command) -> Result<(), Error> {
+        let (load, loaded) = oneshot::channel();
+        let _ = self.jobs.send(Job::Load(guild, load));
+        let mut game = loaded.await.map_err(|_| "The storage stopped")??;
+        let was = game.as_ref().map(|g| g.engine.state);
+        let mut outbox = game.as_ref().map_or_else(Outbox::default, Outbox::new);
+        if handle(&mut game, &mut outbox, command) {
+            let _ = self.jobs.send(Job::Save(Box::new(Save {
+                guild,
+                game: game.clone(),
+                was,
+            })));
+        }
+        if !outbox.is_empty() {
+            tokio::spawn((self.dispatch)(outbox));
+        }
+        if is_live(&game) {
+            let actor = self.spawn(guild, game);
+            self.actors.lock().unwrap().insert(guild, actor);
+        }
+        Ok(())
+    }
+
+    /// Change the game of `guild` once the commands sent before were handled, saving it if
+    /// the outcome says it changed
+    pub async fn edit<T: Outcome + Send + 'static>(
+        &self,
+        guild: GuildId,
+        edit: impl FnOnce(&mut Option<GuildConfig>, &mut Outbox) -> T + Send + 'static,
+    ) -> Result<T, Error> {
+        self.ask(guild, |reply| {
+            Command::Edit(Box::new(move |game, outbox| {
+                let outcome = edit(game, outbox);
+                let changed = outcome.changed();
+                let _ = reply.send(outcome);
+                changed
+            }))
+        })
+        .await
+    }
+
+    /// Copy of the game of `guild`
+    pub async fn get(&self, guild: GuildId) -> Result<Option<GuildConfig>, Error> {
+        self.ask(guild, |reply| {
+            Command::Peek(Box::new(move |game| {
+                let _ = reply.send(game.cloned());
+            }))
+        })
+        .await
+    }
+}
+/// Apply a command to the game, returning whether it changed
+fn handle(game: &mut Option<GuildConfig>, outbox: &mut Outbox, command: Command) -> bool {
+    match command {
+        Command::Guess {
+            message,
+            shown,
+            roles,
+        } => game
+            .as_mut()
+            .is_some_and(|game| blindtest::guess(game, outbox, &message, &shown, &roles)),
+        Command::Skip { admin, reply } => {
+            let skipped = match game {
+                Some(game) => blindtest::skip(game, outbox, admin),
+                None => Err("No game is running"),
+            };
+            let changed = skipped.changed();
+            let _ = reply.send(skipped);
+            changed
+        }
+        Command::Pause {
+            admin,
+            paused,
+            reply,
+        } => {
+            let paused = match game {
+                Some(game) => blindtest::pause(game, outbox, admin, paused),
+                None => Err("No game is running"),
+            };
+            let changed = paused.changed();
+            let _ = reply.send(paused);
+            changed
+        }
+        Command::AddTeam {
+            team,
+            late_start,
+            admin,
+            reply,
+        } => {
+            let added = match game {
+                Some(game) => blindtest::add_team(game, outbox, team, late_start, admin),
+                None => Err("No game exists"),
+            };
+            let changed = added.changed();
+            let _ = reply.send(added);
+            changed
+        }
+        Command::Tick => {
+            if let Some(game) = game {
+                buzzer::tick(game, outbox);
+                blindtest::tick(game, outbox, TICK.as_secs());
+            }
+            !outbox.is_empty()
+        }
+        Command::Edit(edit) => edit(game, outbox),
+        Command::Peek(peek) => {
+            peek(game.as_ref());
+            false
+        }
+    }
+}
+
+/// State of a game after a command changed it, to be written to the storage
+struct Save {
+    guild: GuildId,
+    game: Option<GuildConfig>,
+    /// State of the game before the command
+    was: Option<GameState>,
+}
+
+/// What the games need from the storage
+enum Job {
+    Save(Box<Save>),
+    /// The stored game of a guild, read once the saves queued before are written
+    Load(GuildId, oneshot::Sender<Result<Option<GuildConfig>, Error>>),
+}
+
+/// Thread doing the jobs in the order they were queued, so that the games don't wait on
+/// the storage
+fn worker(storage: Arc<dyn Storage>) -> mpsc::UnboundedSender<Job> {
+    let (sender, mut jobs) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Some(job) = jobs.blocking_recv() {
+            match job {
+                Job::Save(save) => persist(storage.as_ref(), *save),
+                Job::Load(guild, reply) => {
+                    let _ = reply.send(storage.game(guild));
+                }
+            }
+        }
+    });
+    sender
+}
+
+/// Keep the storage in sync with the game after a command changed it
+fn persist(storage: &dyn Storage, Save { guild, game, was }: Save) {
+    let result = match &game {
+        Some(game) => {
+            storage
+                .save_game(guild, game)
+                .and_then(|()| match (was, game.engine.state) {
+                    (Some(GameState::Started), GameState::Ended) => {
+                        storage.record_game(guild, &crate::stats::GameRecord::new(game))
+                    }
+                    _ => Ok(()),
+                })
+        }
+        None if was.is_some() => storage.delete_game(guild),
+        None => Ok(()),
+    };
+    if let Err(e) = result {
+        println!("Error while saving the game: {:?}", e);
+    }
+}
+
+/// Handle the commands sent to the game of a guild, the messages it produces being
+/// dispatched in order by another task. The task stops once the game was ended or deleted
+/// and no command is waiting
+async fn run(
+    guild: GuildId,
+    mut game: Option<GuildConfig>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    actors: Actors,
+    jobs: mpsc::UnboundedSender<Job>,
+    dispatch: Dispatch,
+) {
+    let (outbound, mut outboxes) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(outbox) = outboxes.recv().await {
+            dispatch(outbox).await;
+        }
+    });
+    let mut ticks = tokio::time::interval(TICK);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        let command = tokio::select! {
+            command = commands.recv() => match command {
+                Some(command) => command,
+                None => break,
+            },
+            _ = ticks.tick() => {
+                if !game.as_ref().is_some_and(|g| g.engine.is_running()) {
+                    continue;
+                }
+                Command::Tick
+            }
+        };
+        let was = game.as_ref().map(|g| g.engine.state);
+        let mut outbox = game.as_ref().map_or_else(Outbox::default, Outbox::new);
+        if handle(&mut game, &mut outbox, command) {
+            let _ = jobs.send(Job::Save(Box::new(Save {
+                guild,
+                game: game.clone(),
+                was,
+            })));
+        }
+        if !outbox.is_empty() {
+            let _ = outbound.send(outbox);
+        }
+        if !is_live(&game) {
+            // Commands are sent while holding the lock, none can be lost
+            let mut actors = actors.lock().unwrap();
+            if commands.is_empty() {
+                actors.remove(&guild);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serenity::all::{ChannelId, MessageId, Timestamp};
+
+    use super::*;
+    use crate::config::{Answer, BuzzHolder, BuzzPress, Buzzer, Question};
+    use crate::engine::Engine;
+    use crate::events::GameEvent;
+    use crate::storage::MemoryStorage;
+
+    const GUILDS: u64 = 50;
+    const TEAMS: u64 = 4;
+    const GUESSES: u64 = 100;
+
+    fn game(guild: u64) -> GuildConfig {
+        let questions = (0..20)
+            .map(|i| Question {
+                name: format!("question {i}"),
+                answer: (0..5)
+                    .map(|a| Answer::SingleAnswer(format!("song {a}")))
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        GuildConfig {
+            id: guild,
+            engine: Engine {
+                state: GameState::Started,
+                questions: crate::config::prepare_questions(questions),
+                question_number: 1,
+                paused: false,
+                time_left: None,
+            },
+            teams: (0..TEAMS)
+                .map(|t| Team {
+                    name: format!("team {t}"),
+                    channel: ChannelId::new(guild * 100 + t + 1),
+                    leaderboard: HashMap::new(),
+                    total_points: 0.0,
+                    role: None,
+                    disqualified: None,
+                })
+                .collect(),
+            admin_channel: ChannelId::new(guild * 100 + 99),
+            buzzer: None,
+            adjustments: Vec::new(),
+            found: Vec::new(),
+            question_opened_at: Timestamp::now(),
+            events: Vec::new(),
+            provisioned: Default::default(),
+            signups: Vec::new(),
+            guessed_in: HashMap::new(),
+            settings: Default::default(),
+        }
+    }
+
+    fn guess(guild: u64, i: u64, content: &str) -> Command {
+        let mut message = Message::default();
+        message.id = MessageId::new(guild * 1000 + i + 1);
+        message.channel_id = ChannelId::new(guild * 100 + i % TEAMS + 1);
+        message.author.id = UserId::new(i + 1);
+        message.timestamp = Timestamp::now();
+        message.content = content.to_owned();
+        Command::Guess {
+            shown: message.content.clone(),
+            message: Box::new(message),
+            roles: Vec::new(),
+        }
+    }
+
+    /// Messages sent by the games of a test, which can hold them back
+    #[derive(Default)]
+    struct Sends {
+        count: AtomicUsize,
+        /// The messages wait while the test holds its write lock
+        gate: tokio::sync::RwLock<()>,
+    }
+
+    /// Games started from `games`, their messages being counted in `sent`
+    fn start(games: impl IntoIterator<Item = GuildConfig>, sent: Arc<Sends>) -> Arc<Games> {
+        let storage = Arc::new(MemoryStorage::new());
+        for game in games {
+            storage.save_game(GuildId::new(game.id), &game).unwrap();
+        }
+        let dispatch: Dispatch = Arc::new(move |_| {
+            let sent = sent.clone();
+            Box::pin(async move {
+                let _open = sent.gate.read().await;
+                sent.count.fetch_add(1, Ordering::Relaxed);
+            })
+        });
+        Arc::new(Games::new(storage, dispatch).unwrap())
+    }
+
+    /// Every guild receives its guesses at once, the games handle them without waiting
+    /// for the messages they produce to be sent
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_guesses() {
+        let sent = Arc::new(Sends::default());
+        // No message can be sent until every guess was handled
+        let closed = sent.gate.write().await;
+        let games = start((1..=GUILDS).map(game), sent.clone());
+        let handles: Vec<_> = (1..=GUILDS)
+            .flat_map(|g| (0..GUESSES).map(move |i| (g, i)))
+            .map(|(g, i)| {
+                let games = games.clone();
+                tokio::spawn(async move {
+                    games.send(GuildId::new(g), guess(g, i, &format!("song {}", i % 7)));
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        for g in 1..=GUILDS {
+            let game = games.get(GuildId::new(g)).await.unwrap().unwrap();
+            let points: f64 = game.teams.iter().map(|t| t.total_points).sum();
+            assert_eq!(points, game.found.iter().map(|f| f.points).sum::<f64>());
+            assert!(!game.found.is_empty());
+        }
+        assert_eq!(sent.count.load(Ordering::Relaxed), 0);
+        drop(closed);
+    }
+
+    /// Commands are handled in the order they were sent
+    #[tokio::test]
+    async fn paused_game_ignores_guesses() {
+        let games = start([game(1)], Default::default());
+        let guild = GuildId::new(1);
+        let admin = UserId::new(1);
+        let pause = |paused| {
+            let games = games.clone();
+            async move {
+                games
+                    .ask(guild, |reply| Command::Pause {
+                        admin,
+                        paused,
+                        reply,
+                    })
+                    .await
+                    .unwrap()
+            }
+        };
+        pause(true).await.unwrap();
+        assert!(pause(true).await.is_err());
+        games.send(guild, guess(1, 0, "song 0"));
+        pause(false).await.unwrap();
+        games.send(guild, guess(1, 1, "song 1"));
+        let game = games.get(guild).await.unwrap().unwrap();
+        assert_eq!(game.found.len(), 1);
+        assert_eq!(game.found[0].answer, "song 1");
+    }
+
+    /// A player can only answer for the team they first guessed in
+    #[tokio::test]
+    async fn players_stay_in_their_team() {
+        let games = start([game(1)], Default::default());
+        let guild = GuildId::new(1);
+        // The same player guesses in the channel of team 0, then in the one of team 1
+        let mut other = guess(1, 0, "song 0");
+        if let Command::Guess { message, .. } = &mut other {
+            message.id = MessageId::new(999);
+            message.channel_id = ChannelId::new(102);
+        }
+        games.send(guild, guess(1, 0, "nothing"));
+        games.send(guild, other);
+        let game = games.get(guild).await.unwrap().unwrap();
+        assert!(game.found.is_empty());
+        assert_eq!(game.guessed_in[&UserId::new(1)], "team 0");
+        games.send(guild, guess(1, 0, "song 0"));
+        let game = games.get(guild).await.unwrap().unwrap();
+        assert_eq!(game.found[0].team, "team 0");
+    }
+
+    /// A wrong guess is logged, so the game is saved right away
+    #[test]
+    fn wrong_guesses_are_saved() {
+        let mut game = game(1);
+        let Command::Guess {
+            message,
+            shown,
+            roles,
+        } = guess(1, 0, "nothing")
+        else {
+            unreachable!()
+        };
+        let mut outbox = Outbox::new(&game);
+        assert!(blindtest::guess(
+            &mut game,
+            &mut outbox,
+            &message,
+            &shown,
+            &roles
+        ));
+        assert!(game.found.is_empty());
+        assert!(matches!(game.events[0].event, GameEvent::Guess { .. }));
+        // Messages out of the team channels aren't guesses
+        let mut outside = message.clone();
+        outside.channel_id = ChannelId::new(1);
+        assert!(!blindtest::guess(
+            &mut game,
+            &mut outbox,
+            &outside,
+            &shown,
+            &roles
+        ));
+        assert_eq!(game.events.len(), 1);
+    }
+
+    /// The team holding the buzzer loses it once its time is up
+    #[tokio::test]
+    async fn buzzer_runs_out_of_time() {
+        let mut started = game(1);
+        let team = started.teams[0].channel;
+        let mut buzzer = Buzzer::new(5);
+        buzzer.presses.push(BuzzPress {
+            channel: team,
+            user: UserId::new(1),
+            at: Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() - 10).unwrap(),
+        });
+        buzzer.holder = Some(BuzzHolder {
+            channel: team,
+            press: 0,
+            paused_secs: 0,
+        });
+        started.buzzer = Some(buzzer);
+        let games = start([started], Default::default());
+        games.send(GuildId::new(1), Command::Tick);
+        let game = games.get(GuildId::new(1)).await.unwrap().unwrap();
+        let buzzer = game.buzzer.unwrap();
+        assert!(buzzer.holder.is_none());
+        assert_eq!(buzzer.locked_out, [team]);
+    }
+
+    /// Only the games being played have a task, it stops once its game is ended or deleted
+    #[tokio::test]
+    async fn tasks_follow_the_games() {
+        let mut ended = game(2);
+        ended.engine.state = GameState::Ended;
+        let games = start([game(1), ended], Default::default());
+        let (playing, ended, idle) = (GuildId::new(1), GuildId::new(2), GuildId::new(3));
+        assert!(games.is_live(playing));
+        assert!(!games.is_live(ended));
+
+        // Guilds without a game ignore guesses, asking about them starts nothing
+        games.send(idle, guess(3, 0, "song 0"));
+        assert!(games.get(idle).await.unwrap().is_none());
+        assert!(!games.is_live(idle));
+        assert_eq!(games.get(ended).await.unwrap().unwrap().id, 2);
+        assert!(!games.is_live(ended));
+
+        // Ending the game stops its task, the stored game is still there
+        games
+            .edit(playing, |game, _| {
+                game.as_mut().unwrap().engine.state = GameState::Ended;
+            })
+            .await
+            .unwrap();
+        tokio::task::yield_now().await;
+        assert!(!games.is_live(playing));
+        let game = games.get(playing).await.unwrap().unwrap();
+        assert!(matches!(game.engine.state, GameState::Ended));
+
+        // Creating a game starts a task, deleting it stops it
+        games
+            .edit(idle, |game, _| *game = Some(super::tests::game(3)))
+            .await
+            .unwrap();
+        assert!(games.is_live(idle));
+        games.edit(idle, |game, _| *game = None).await.unwrap();
+        tokio::task::yield_now().await;
+        assert!(!games.is_live(idle));
+        assert!(games.get(idle).await.unwrap().is_none());
+    }
+}
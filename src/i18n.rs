@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::settings::Language;
+use crate::{Context, Data, Error};
+
+/// Translations of the bot messages, keyed by their English text
+#[derive(Deserialize)]
+struct Catalog {
+    /// Discord locale the catalog is used for
+    locale: String,
+    messages: HashMap<String, String>,
+    /// Names of the slash commands and of their parameters
+    names: HashMap<String, String>,
+}
+
+fn catalog(language: Language) -> Option<&'static Catalog> {
+    static FRENCH: OnceLock<Catalog> = OnceLock::new();
+    match language {
+        Language::English => None,
+        Language::French => Some(FRENCH.get_or_init(|| {
+            serde_json::from_str(include_str!("../locales/fr.json"))
+                .expect("The French catalog is invalid")
+        })),
+    }
+}
+
+impl Language {
+    /// Language matching a Discord locale, like `fr` or `en-US`
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        match locale.split('-').next()? {
+            "en" => Some(Self::English),
+            "fr" => Some(Self::French),
+            _ => None,
+        }
+    }
+}
+
+/// Text in `language`, the English one if it has no translation
+pub fn translate(language: Language, text: &str) -> &str {
+    catalog(language)
+        .and_then(|c| c.messages.get(text))
+        .map(String::as_str)
+        .unwrap_or(text)
+}
+
+/// Replace the `{name}` placeholders of a translated text in a single pass, the values
+/// being copied as they are so that a placeholder they contain is never filled
+pub fn fill(text: &str, args: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest[1..].find('}').and_then(|end| {
+            let name = &rest[1..end + 1];
+            let (_, value) = args.iter().find(|(n, _)| *n == name)?;
+            Some((value, end + 2))
+        });
+        match value {
+            Some((value, len)) => {
+                filled.push_str(value);
+                rest = &rest[len..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Translate a message, filling its `{name}` placeholders with the given values
+#[macro_export]
+macro_rules! tr {
+    ($lang:expr, $text:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($lang, $text),
+            &[$((stringify!($name), ($value).to_string())),*],
+        )
+    };
+}
+
+/// Language of the guild the command was sent in. The replies of the commands are seen by
+/// everyone in the channel, so they are written in the guild language rather than in the
+/// one of the member that sent the command
+pub fn lang(ctx: Context<'_>) -> Language {
+    crate::settings::current(ctx).language
+}
+
+/// Language of the member that sent the command, for the replies only they can see. The
+/// guild language is used when their Discord locale has no translation
+pub fn user_lang(ctx: Context<'_>) -> Language {
+    ctx.locale()
+        .and_then(Language::from_locale)
+        .unwrap_or_else(|| lang(ctx))
+}
+
+/// Add the translations of the names and descriptions of the commands
+pub fn localize_commands(commands: &mut [poise::Command<Data, Error>]) {
+    for language in [Language::French] {
+        let Some(catalog) = catalog(language) else {
+            continue;
+        };
+        localize(commands, catalog);
+    }
+}
+
+fn localize(commands: &mut [poise::Command<Data, Error>], catalog: &Catalog) {
+    let locale = &catalog.locale;
+    let name = |n: &str| catalog.names.get(n).cloned();
+    let description = |d: Option<&str>| d.and_then(|d| catalog.messages.get(d)).cloned();
+    for command in commands {
+        if let Some(n) = name(&command.name) {
+            command.name_localizations.insert(locale.clone(), n);
+        }
+        if let Some(d) = description(command.description.as_deref()) {
+            command.description_localizations.insert(locale.clone(), d);
+        }
+        for parameter in &mut command.parameters {
+            if let Some(n) = name(&parameter.name) {
+                parameter.name_localizations.insert(locale.clone(), n);
+            }
+            if let Some(d) = description(parameter.description.as_deref()) {
+                parameter
+                    .description_localizations
+                    .insert(locale.clone(), d);
+            }
+            for choice in &mut parameter.choices {
+                if let Some(n) = description(Some(&choice.name)) {
+                    choice.localizations.insert(locale.clone(), n);
+                }
+            }
+        }
+        localize(&mut command.subcommands, catalog);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        args.iter().map(|(n, v)| (*n, (*v).to_owned())).collect()
+    }
+
+    #[test]
+    fn fills_known_placeholders() {
+        let text = "{user} has {points} points {unknown} {";
+        let filled = fill(text, &args(&[("user", "Ann"), ("points", "2")]));
+        assert_eq!(filled, "Ann has 2 points {unknown} {");
+    }
+
+    #[test]
+    fn values_are_copied_verbatim() {
+        let text = "{user} found `{answer}`";
+        let filled = fill(text, &args(&[("user", "{answer}"), ("answer", "{user}")]));
+        assert_eq!(filled, "{answer} found `{user}`");
+    }
+
+    #[test]
+    fn translations_have_the_same_placeholders() {
+        let catalog = catalog(Language::French).unwrap();
+        let placeholders = |text: &str| {
+            let mut names: Vec<_> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|p| p.split_once('}').map(|(n, _)| n.to_owned()))
+                .collect();
+            names.sort();
+            names
+        };
+        for (english, french) in &catalog.messages {
+            assert_eq!(placeholders(english), placeholders(french), "{english}");
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::Write;
+
+use crate::config::{Answer, Question};
+use crate::settings::Language;
+use crate::{tr, Error};
+
+/// Columns of a spreadsheet quiz pack, any of the names of a column can be its header
+const COLUMNS: [(Column, &[&str]); 7] = [
+    (Column::Name, &["name"]),
+    (Column::Artist, &["artist aliases", "artist"]),
+    (Column::Title, &["title aliases", "title"]),
+    (Column::Year, &["year"]),
+    (Column::Audio, &["audio file", "audio"]),
+    (Column::Hint, &["hint"]),
+    (Column::TimeLimit, &["time limit"]),
+];
+
+/// Separates the names of an artist or of a title in a cell, and the other names of an
+/// answer given to `/quiz answer`
+pub const ALIAS_SEPARATOR: char = '|';
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Name,
+    Artist,
+    Title,
+    Year,
+    Audio,
+    Hint,
+    TimeLimit,
+}
+
+/// Why a spreadsheet can't be turned into a quiz pack, rows are numbered like in the
+/// spreadsheet, the headers being on row 1
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportError {
+    Unreadable {
+        row: u64,
+        error: String,
+    },
+    UnknownColumn {
+        column: usize,
+        header: String,
+    },
+    MissingName,
+    EmptyName {
+        row: u64,
+    },
+    NoAnswer {
+        row: u64,
+    },
+    NotANumber {
+        row: u64,
+        header: String,
+        value: String,
+    },
+}
+
+impl ImportError {
+    pub fn describe(&self, lang: Language) -> String {
+        match self {
+            Self::Unreadable { row, error } => tr!(
+                lang,
+                "Row {row}: the row can't be read: {error}",
+                row = row,
+                error = error
+            ),
+            Self::UnknownColumn { column, header } => tr!(
+                lang,
+                "Column {column}: `{header}` isn't a known column, use {columns}",
+                column = column,
+                header = header,
+                columns = COLUMNS
+                    .iter()
+                    .map(|(_, names)| format!("`{}`", names[0]))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::MissingName => tr!(lang, "The `name` column is missing"),
+            Self::EmptyName { row } => tr!(
+                lang,
+                "Row {row}, column `name`: the question has no name",
+                row = row
+            ),
+            Self::NoAnswer { row } => tr!(
+                lang,
+                "Row {row}: the question has neither an artist nor a title",
+                row = row
+            ),
+            Self::NotANumber { row, header, value } => tr!(
+                lang,
+                "Row {row}, column `{header}`: `{value}` isn't a whole number",
+                row = row,
+                header = header,
+                value = value
+            ),
+        }
+    }
+}
+
+/// An answer found by any of the names of `cell`
+fn answer(cell: &str) -> Option<Answer> {
+    let names: Vec<String> = cell
+        .split(ALIAS_SEPARATOR)
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+        .collect();
+    match names.len() {
+        0 => None,
+        1 => names.into_iter().next().map(Answer::SingleAnswer),
+        len => Some(Answer::MutlipleAnswer(names, len)),
+    }
+}
+
+/// The whole number in the cell of `header`, recording an error if it isn't one
+fn number<T: std::str::FromStr>(
+    row: u64,
+    header: &str,
+    cell: &str,
+    errors: &mut Vec<ImportError>,
+) -> Option<T> {
+    let n = cell.parse().ok();
+    if n.is_none() {
+        errors.push(ImportError::NotANumber {
+            row,
+            header: header.to_owned(),
+            value: cell.to_owned(),
+        });
+    }
+    n
+}
+
+/// Questions of a CSV or TSV spreadsheet with a header row, the delimiter is guessed from
+/// the headers. Every mistake of the spreadsheet is reported at once
+pub fn read_pack(data: &[u8]) -> Result<Vec<Question>, Vec<ImportError>> {
+    let header = data.split(|b| *b == b'\n').next().unwrap_or_default();
+    let delimiter = if header.contains(&b'\t') { b'\t' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(data);
+    let unreadable = |e: csv::Error| ImportError::Unreadable {
+        row: e.position().map_or(1, |p| p.line()),
+        error: e.to_string(),
+    };
+    let headers = reader.headers().map_err(|e| vec![unreadable(e)])?.clone();
+    let mut errors = Vec::new();
+    let mut columns = Vec::new();
+    for (i, header) in headers.iter().enumerate() {
+        let name = Question::normalize_string(header);
+        match COLUMNS
+            .iter()
+            .find(|(_, names)| names.contains(&name.as_str()))
+        {
+            Some((column, _)) => columns.push(Some(*column)),
+            None if name.is_empty() => columns.push(None),
+            None => {
+                errors.push(ImportError::UnknownColumn {
+                    column: i + 1,
+                    header: header.to_owned(),
+                });
+                columns.push(None);
+            }
+        }
+    }
+    if !columns.contains(&Some(Column::Name)) {
+        errors.push(ImportError::MissingName);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut questions = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(unreadable(e));
+                continue;
+            }
+        };
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let row = record.position().map_or(0, |p| p.line());
+        let mut question = Question::default();
+        for ((column, cell), header) in columns.iter().zip(&record).zip(&headers) {
+            let cell = cell.trim();
+            let Some(column) = column.filter(|_| !cell.is_empty()) else {
+                continue;
+            };
+            match column {
+                Column::Name => question.name = cell.to_owned(),
+                Column::Artist | Column::Title => question.answer.extend(answer(cell)),
+                Column::Year => question.year = number(row, header, cell, &mut errors),
+                Column::Audio => question.audio = Some(cell.to_owned()),
+                Column::Hint => question.hint = Some(cell.to_owned()),
+                Column::TimeLimit => question.time_limit = number(row, header, cell, &mut errors),
+            }
+        }
+        if question.name.is_empty() {
+            errors.push(ImportError::EmptyName { row });
+        }
+        if question.answer.is_empty() {
+            errors.push(ImportError::NoAnswer { row });
+        }
+        questions.push(question);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(questions)
+}
+
+/// `answer-bot import FILE [OUTPUT]`: write the json quiz pack of a spreadsheet to
+/// `OUTPUT`, or print it
+pub fn run(path: Option<String>, output: Option<String>) -> Result<bool, Error> {
+    let path = path.ok_or("Give the spreadsheet to import")?;
+    let questions = match read_pack(&std::fs::read(path)?) {
+        Ok(questions) => questions,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error.describe(Language::English));
+            }
+            return Ok(false);
+        }
+    };
+    let json = serde_json::to_vec_pretty(&questions)?;
+    match output {
+        Some(output) => std::fs::write(&output, json)?,
+        None => writeln!(std::io::stdout(), "{}", String::from_utf8(json)?)?,
+    }
+    eprintln!("Imported {} questions", questions.len());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_column() {
+        let csv = "Name,Artist aliases,Title aliases,Year,Audio file,Hint,Time limit\n\
+                   Song 1,Queen,Bohemian Rhapsody | Bo Rhap,1975,songs/1.mp3,Opera,30\n\
+                   ,,,,,,\n\
+                   Song 2,,Imagine,,,,\n";
+        let questions = read_pack(csv.as_bytes()).unwrap();
+        assert_eq!(questions.len(), 2);
+        let first = &questions[0];
+        assert_eq!(first.name, "Song 1");
+        assert!(matches!(&first.answer[0], Answer::SingleAnswer(a) if a == "Queen"));
+        assert!(matches!(
+            &first.answer[1],
+            Answer::MutlipleAnswer(names, 2) if names == &["Bohemian Rhapsody", "Bo Rhap"]
+        ));
+        assert_eq!(first.year, Some(1975));
+        assert_eq!(first.audio.as_deref(), Some("songs/1.mp3"));
+        assert_eq!(first.hint.as_deref(), Some("Opera"));
+        assert_eq!(first.time_limit, Some(30));
+        assert_eq!(questions[1].answer.len(), 1);
+        assert_eq!(questions[1].year, None);
+    }
+
+    #[test]
+    fn guesses_tabs_from_the_headers() {
+        let tsv = "name\ttitle\nSong, with a comma\tYesterday\n";
+        let questions = read_pack(tsv.as_bytes()).unwrap();
+        assert_eq!(questions[0].name, "Song, with a comma");
+    }
+
+    #[test]
+    fn reports_unknown_and_missing_columns() {
+        let errors = read_pack(b"title,colour\nYesterday,red\n").unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                ImportError::UnknownColumn {
+                    column: 2,
+                    header: "colour".to_owned()
+                },
+                ImportError::MissingName
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_every_bad_row() {
+        let csv = "name,artist,year,time limit\n\
+                   Song 1,Queen,soon,30\n\
+                   ,Queen,,\n\
+                   Song 3,,,-5\n";
+        let errors = read_pack(csv.as_bytes()).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                ImportError::NotANumber {
+                    row: 2,
+                    header: "year".to_owned(),
+                    value: "soon".to_owned()
+                },
+                ImportError::EmptyName { row: 3 },
+                ImportError::NotANumber {
+                    row: 4,
+                    header: "time limit".to_owned(),
+                    value: "-5".to_owned()
+                },
+                ImportError::NoAnswer { row: 4 },
+            ]
+        );
+        assert_eq!(
+            errors[0].describe(Language::English),
+            "Row 2, column `year`: `soon` isn't a whole number"
+        );
+    }
+}
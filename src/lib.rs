@@ -0,0 +1,34 @@
+#![warn(clippy::str_to_string)]
+
+//! The blind test bot, its binaries only start it or its tools
+
+pub mod blindtest;
+pub mod buzzer;
+pub mod config;
+pub mod engine;
+pub mod events;
+pub mod export;
+pub mod games;
+pub mod i18n;
+pub mod import;
+pub mod lint;
+pub mod quiz;
+pub mod results;
+pub mod settings;
+pub mod stats;
+pub mod storage;
+pub mod teams;
+pub mod templates;
+pub mod utils;
+
+use std::sync::Arc;
+
+// Types used by all command functions
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Context<'a> = poise::Context<'a, Data, Error>;
+
+// Custom user data passed to all command functions
+pub struct Data {
+    pub games: Arc<games::Games>,
+    pub storage: Arc<dyn storage::Storage>,
+}
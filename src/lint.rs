@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::{Answer, Question};
+use crate::settings::Language;
+use crate::{tr, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The pack can't be played as intended
+    Error,
+    /// The pack can be played but it's likely a mistake
+    Warning,
+}
+
+/// A mistake in a quiz pack, questions are numbered from 1 like in the game
+#[derive(Clone, Debug, PartialEq)]
+pub enum Issue {
+    NoQuestions,
+    NoAnswers {
+        question: usize,
+    },
+    EmptyName {
+        question: usize,
+    },
+    SameName {
+        question: usize,
+        other: usize,
+    },
+    EmptyAnswer {
+        question: usize,
+    },
+    SameAnswer {
+        question: usize,
+        answer: String,
+    },
+    SharedAnswer {
+        question: usize,
+        other: usize,
+        answer: String,
+    },
+    MissingAudio {
+        question: usize,
+        path: String,
+    },
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::NoQuestions
+            | Self::NoAnswers { .. }
+            | Self::EmptyAnswer { .. }
+            | Self::MissingAudio { .. } => Severity::Error,
+            Self::EmptyName { .. }
+            | Self::SameName { .. }
+            | Self::SameAnswer { .. }
+            | Self::SharedAnswer { .. } => Severity::Warning,
+        }
+    }
+
+    /// What is wrong, followed by how to fix it
+    pub fn describe(&self, lang: Language) -> (String, String) {
+        match self {
+            Self::NoQuestions => (
+                tr!(lang, "The pack has no question"),
+                tr!(lang, "Add questions to the pack"),
+            ),
+            Self::NoAnswers { question } => (
+                tr!(
+                    lang,
+                    "Question {question} has no answer",
+                    question = question
+                ),
+                tr!(lang, "Add its answers or remove the question"),
+            ),
+            Self::EmptyName { question } => (
+                tr!(lang, "Question {question} has no name", question = question),
+                tr!(lang, "Give it a name, it is shown when the question opens"),
+            ),
+            Self::SameName { question, other } => (
+                tr!(
+                    lang,
+                    "Question {question} has the same name as question {other}",
+                    question = question,
+                    other = other
+                ),
+                tr!(lang, "Rename one of them so the admins can tell them apart"),
+            ),
+            Self::EmptyAnswer { question } => (
+                tr!(
+                    lang,
+                    "Question {question} has an empty answer",
+                    question = question
+                ),
+                tr!(
+                    lang,
+                    "Write the answer or remove it, the question can't be completed otherwise"
+                ),
+            ),
+            Self::SameAnswer { question, answer } => (
+                tr!(
+                    lang,
+                    "Question {question} has the answer `{answer}` twice",
+                    question = question,
+                    answer = answer
+                ),
+                tr!(
+                    lang,
+                    "Keep only one of them, the same guess would be counted twice"
+                ),
+            ),
+            Self::SharedAnswer {
+                question,
+                other,
+                answer,
+            } => (
+                tr!(
+                    lang,
+                    "`{answer}` answers both question {other} and question {question}",
+                    answer = answer,
+                    other = other,
+                    question = question
+                ),
+                tr!(lang, "Check one of them isn't a copy of the other"),
+            ),
+            Self::MissingAudio { question, path } => (
+                tr!(
+                    lang,
+                    "The audio file `{path}` of question {question} doesn't exist",
+                    path = path,
+                    question = question
+                ),
+                tr!(lang, "Check the path, it is relative to the pack"),
+            ),
+        }
+    }
+}
+
+/// Mistakes of a quiz pack as written in its file, the audio files are looked for in
+/// `audio_dir` when given
+pub fn check(questions: &[Question], audio_dir: Option<&Path>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if questions.is_empty() {
+        issues.push(Issue::NoQuestions);
+    }
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut answers: HashMap<String, usize> = HashMap::new();
+    for (i, q) in questions.iter().enumerate() {
+        let question = i + 1;
+        let name = Question::normalize_string(&q.name);
+        if name.is_empty() {
+            issues.push(Issue::EmptyName { question });
+        } else if let Some(&other) = names.get(&name) {
+            issues.push(Issue::SameName { question, other });
+        } else {
+            names.insert(name, question);
+        }
+        if q.answer.is_empty() {
+            issues.push(Issue::NoAnswers { question });
+        }
+        let mut seen = Vec::new();
+        for answer in &q.answer {
+            let aliases = match answer {
+                Answer::SingleAnswer(name) => std::slice::from_ref(name),
+                Answer::MutlipleAnswer(aliases, _) => aliases.as_slice(),
+            };
+            if aliases.is_empty() {
+                issues.push(Issue::EmptyAnswer { question });
+            }
+            for alias in aliases {
+                let answer = Question::normalize_string(alias);
+                if answer.is_empty() {
+                    issues.push(Issue::EmptyAnswer { question });
+                } else if seen.contains(&answer) {
+                    issues.push(Issue::SameAnswer { question, answer });
+                } else {
+                    if let Some(&other) = answers.get(&answer) {
+                        issues.push(Issue::SharedAnswer {
+                            question,
+                            other,
+                            answer: answer.clone(),
+                        });
+                    } else {
+                        answers.insert(answer.clone(), question);
+                    }
+                    seen.push(answer);
+                }
+            }
+        }
+        if let (Some(path), Some(dir)) = (&q.audio, audio_dir) {
+            if !dir.join(path).is_file() {
+                issues.push(Issue::MissingAudio {
+                    question,
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Every issue followed by how to fix it, the errors first
+pub fn report(issues: &[Issue], lang: Language) -> Result<String, Error> {
+    let mut s = String::new();
+    let errors = issues.iter().filter(|i| i.severity() == Severity::Error);
+    let warnings = issues.iter().filter(|i| i.severity() == Severity::Warning);
+    for (issue, mark) in errors.map(|i| (i, "❌")).chain(warnings.map(|i| (i, "⚠️"))) {
+        let (what, fix) = issue.describe(lang);
+        writeln!(&mut s, "{mark} {what}\n-> {fix}")?;
+    }
+    Ok(s)
+}
+
+/// `answer-bot check [FILE]`: print the mistakes of a quiz pack, failing if it has errors
+pub fn run(path: Option<String>) -> Result<bool, Error> {
+    let path = path.unwrap_or_else(|| crate::engine::DEFAULT_PACK.to_owned());
+    let path = Path::new(&path);
+    let questions = crate::engine::read_pack(path)?;
+    let issues = check(&questions, path.parent());
+    if issues.is_empty() {
+        println!(
+            "{}: {} questions, no issue found",
+            path.display(),
+            questions.len()
+        );
+    } else {
+        print!("{}", report(&issues, Language::English)?);
+    }
+    Ok(!issues.iter().any(|i| i.severity() == Severity::Error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(name: &str, answers: &[&str]) -> Question {
+        Question {
+            name: name.to_owned(),
+            answer: answers
+                .iter()
+                .map(|a| Answer::SingleAnswer((*a).to_owned()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clean_pack_has_no_issue() {
+        let pack = [question("a", &["one", "two"]), question("b", &["three"])];
+        assert!(check(&pack, None).is_empty());
+    }
+
+    #[test]
+    fn empty_pack_is_an_error() {
+        assert_eq!(check(&[], None), [Issue::NoQuestions]);
+    }
+
+    #[test]
+    fn finds_the_mistakes_of_each_question() {
+        let mut pack = [
+            question("Song", &["One", "  "]),
+            question("song", &["one  ", "ONE"]),
+            question(" ", &[]),
+        ];
+        pack[2].answer.push(Answer::MutlipleAnswer(Vec::new(), 0));
+        assert_eq!(
+            check(&pack, None),
+            [
+                Issue::EmptyAnswer { question: 1 },
+                Issue::SameName {
+                    question: 2,
+                    other: 1
+                },
+                Issue::SharedAnswer {
+                    question: 2,
+                    other: 1,
+                    answer: "one".to_owned()
+                },
+                Issue::SameAnswer {
+                    question: 2,
+                    answer: "one".to_owned()
+                },
+                Issue::EmptyName { question: 3 },
+                Issue::EmptyAnswer { question: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn audio_files_are_looked_for_next_to_the_pack() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut pack = [question("a", &["one"]), question("b", &["two"])];
+        pack[0].audio = Some("Cargo.toml".to_owned());
+        pack[1].audio = Some("missing.mp3".to_owned());
+        assert_eq!(
+            check(&pack, Some(dir)),
+            [Issue::MissingAudio {
+                question: 2,
+                path: "missing.mp3".to_owned()
+            }]
+        );
+        assert!(check(&pack, None).is_empty());
+    }
+
+    #[test]
+    fn report_lists_errors_first() {
+        let pack = [question("a", &["one", "one"]), question("b", &[])];
+        let report = report(&check(&pack, None), Language::English).unwrap();
+        assert!(report.starts_with("❌ Question 2 has no answer\n"));
+        assert!(report.contains("⚠️ Question 1 has the answer `one` twice"));
+    }
+}
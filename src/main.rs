@@ -1,26 +1,15 @@
 #![warn(clippy::str_to_string)]
 
-mod blindtest;
-mod config;
-mod utils;
-
-use ::serenity::all::{FullEvent, GuildId, Mentionable};
-use config::Question;
+use ::serenity::all::FullEvent;
+use answer_bot::{
+    blindtest, buzzer, games, i18n, import, lint, quiz, settings, stats, storage, teams, utils,
+    Context, Data, Error,
+};
 use poise::{serenity_prelude as serenity, BoxFuture};
-use std::{collections::HashMap, env::var, sync::Arc};
-use utils::{broadcast_message, create_embed, send_admin_message};
-
-// Types used by all command functions
-type Error = Box<dyn std::error::Error + Send + Sync>;
-type Context<'a> = poise::Context<'a, Data, Error>;
-
-// Custom user data passed to all command functions
-pub struct Data {
-    games: Arc<tokio::sync::RwLock<HashMap<GuildId, config::GuildConfig>>>,
-}
+use std::{env::var, sync::Arc};
 
 /// Show this help menu
-#[poise::command(slash_command)]
+#[poise::command(slash_command, category = "Player")]
 pub async fn help(
     ctx: Context<'_>,
     #[description = "Specific command to show help about"]
@@ -31,7 +20,11 @@ pub async fn help(
         ctx,
         command.as_deref(),
         poise::builtins::HelpConfiguration {
-            extra_text_at_bottom: "This is an example bot made to showcase features of my custom Discord bot framework",
+            // The help is only shown to the member that asked for it
+            extra_text_at_bottom: i18n::translate(
+                i18n::user_lang(ctx),
+                "This is an example bot made to showcase features of my custom Discord bot framework",
+            ),
             ..Default::default()
         },
     )
@@ -78,149 +71,30 @@ async fn _handle_message(
             let Some(guild_id) = new_message.guild_id else {
                 return Ok(());
             };
-            let channel_id = new_message.channel_id;
-            let author = &new_message.author;
-            let data = _data.games.write().await;
-            if !data
-                .get(&guild_id)
-                .map(|c| matches!(c.state, config::GameState::Started))
-                .unwrap_or_default()
-            {
-                return Ok(());
-            }
-
-            let mut game =
-                tokio::sync::RwLockWriteGuard::map(data, |s| s.get_mut(&guild_id).unwrap());
-            let mut channels = game.teams.iter().map(|t| t.channel).collect::<Vec<_>>();
-            if !channels.contains(&channel_id) {
+            if !_data.games.is_live(guild_id) {
                 return Ok(());
             }
-            channels.push(game.admin_channel);
-
-            let Some(_) = game.questions.last_mut() else {
-                game.state = config::GameState::Ended;
-                return Ok(());
+            let roles = match &new_message.member {
+                Some(member) => member.roles.clone(),
+                None => guild_id.member(ctx, new_message.author.id).await?.roles,
             };
-            if let Some((pos, pts)) = game
-                .questions
-                .last_mut()
-                .unwrap()
-                .get_answer_pos(&new_message.content)
-            {
-                let normalized = config::Question::normalize_string(&new_message.content);
-                let remove = match game.questions.last_mut().unwrap().answer.get_mut(pos) {
-                    Some(config::Answer::MutlipleAnswer(i, _p)) => {
-                        let p = i.iter().position(|s| *s == normalized);
-                        p.and_then(|p| -> Option<()> {
-                            i.swap_remove(p);
-                            None
-                        });
-                        i.is_empty()
-                    }
-                    _ => true,
-                };
-                if remove {
-                    game.questions.last_mut().unwrap().answer.swap_remove(pos);
-                };
-                let team = game
-                    .teams
-                    .iter_mut()
-                    .find(|t| t.channel == channel_id)
-                    .unwrap();
-                *team.leaderboard.entry(author.id).or_default() += pts;
-                team.total_points += pts;
-                broadcast_message(
-                    ctx,
-                    channels.clone(),
-                    create_embed(
-                        (0, 255, 0),
-                        "Answer found !",
-                        format!(
-                            "{} found an answer !\nIt was: `{}`\nThey now have {} points !",
-                            new_message.author.id.mention(),
-                            Question::normalize_string(&new_message.content_safe(ctx)),
-                            team.total_points
-                        ),
-                    ),
-                )
-                .await?;
-            }
-
-            if game.questions.last_mut().unwrap().answer.is_empty() {
-                game.questions.pop();
-                let is_finished = game.questions.is_empty();
-
-                broadcast_message(
-                    ctx,
-                    channels.clone(),
-                    create_embed(
-                        (0, 255, 0),
-                        "All answer found",
-                        if is_finished {
-                            "The game is finished\n Hope you had fun !"
-                        } else {
-                            "All anser were found for the current questions !"
-                        },
-                    ),
-                )
-                .await?;
-                if !is_finished {
-                    send_admin_message(
-                        &ctx,
-                        game.admin_channel,
-                        create_embed((0, 0, 255), "Next question !", {
-                            use std::fmt::Write;
-                            let mut s = String::new();
-                            writeln!(
-                                &mut s,
-                                "Here are the next answers for the question: `{}`\n",
-                                game.questions.last().unwrap().name
-                            )?;
-                            for rep in &game.questions.last().unwrap().answer {
-                                match rep {
-                                    config::Answer::SingleAnswer(srep) => {
-                                        writeln!(&mut s, "-> `{srep}`")?;
-                                    }
-                                    config::Answer::MutlipleAnswer(rep_alias, _) => {
-                                        if rep_alias.len() == 1 {
-                                            writeln!(&mut s, "-> `{}`", rep_alias[0])?;
-                                        } else {
-                                            let mut iter = rep_alias.iter().peekable();
-                                            if let Some(r) = iter.next() {
-                                                writeln!(&mut s, "┌ -> `{r}`")?;
-                                            }
-                                            while let Some(r) = iter.next() {
-                                                if iter.peek().is_none() {
-                                                    writeln!(&mut s, "└ -> `{r}`")?;
-                                                } else {
-                                                    writeln!(&mut s, "│ -> `{r}`")?;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            writeln!(
-                                &mut s,
-                                "\nThere are {} remaining questions",
-                                game.questions.len() - 1
-                            )?;
-                            s
-                        }),
-                    )
-                    .await?;
-                } else {
-                    send_admin_message(
-                        &ctx,
-                        game.admin_channel,
-                        create_embed((0, 0, 255), "Game is finished !", "Hope it was fun!"),
-                    )
-                    .await?;
-                }
-            }
-
+            _data.games.send(
+                guild_id,
+                games::Command::Guess {
+                    message: Box::new(new_message.clone()),
+                    shown: new_message.content_safe(ctx),
+                    roles,
+                },
+            );
             Ok(())
         }
+        FullEvent::InteractionCreate { interaction } => {
+            let Some(interaction) = interaction.as_message_component() else {
+                return Ok(());
+            };
+            buzzer::handle_interaction(ctx, interaction, _data).await?;
+            teams::handle_interaction(ctx, interaction, _data).await
+        }
         _ => Ok(()),
     }
 }
@@ -229,29 +103,43 @@ async fn _handle_message(
 async fn main() {
     env_logger::init();
 
+    let mut args = std::env::args().skip(1);
+    let run = match args.next().as_deref() {
+        Some("check") => Some(lint::run(args.next())),
+        Some("import") => Some(import::run(args.next(), args.next())),
+        _ => None,
+    };
+    match run {
+        None => {}
+        Some(Ok(true)) => return,
+        Some(Ok(false)) => std::process::exit(1),
+        Some(Err(e)) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+    }
+
     // Framework kOptions contains allof poise's configuration option in one struct
     // Every option can be omitted to use its default value
     let options = poise::FrameworkOptions {
-        commands: vec![help(), blindtest::game_cmd()],
+        commands: {
+            let mut commands = vec![
+                help(),
+                blindtest::game_cmd(),
+                quiz::quiz_cmd(),
+                stats::stats_cmd(),
+                stats::leaderboard_cmd(),
+                stats::season_cmd(),
+                settings::config_cmd(),
+            ];
+            i18n::localize_commands(&mut commands);
+            commands
+        },
         prefix_options: Default::default(),
         on_error: |error| Box::pin(on_error(error)),
         pre_command: |_ctx| Box::pin(async {}),
         post_command: |_ctx| Box::pin(async {}),
-        command_check: Some(|ctx| {
-            Box::pin(async move {
-                let (guild, role) = {
-                    let Some(guild) = ctx.guild() else {
-                        return Ok(false);
-                    };
-                    let Some(role) = guild.role_by_name("Tutors") else {
-                        eprintln!("There is no role named tutors !");
-                        return Ok(false);
-                    };
-                    (guild.id, role.id)
-                };
-                Ok(ctx.author().has_role(ctx, guild, role).await?)
-            })
-        }),
+        command_check: Some(|ctx| Box::pin(settings::check(ctx))),
         // Enforce command checks even for owners (enforced by default)
         // Set to true to bypass checks, which is useful for testing
         skip_checks_for_owners: false,
@@ -259,14 +147,28 @@ async fn main() {
         ..Default::default()
     };
 
+    let data_dir = std::path::PathBuf::from(var("DATA_DIR").unwrap_or_else(|_| "./data".into()));
+    let storage: Arc<dyn storage::Storage> = match var("STORAGE").as_deref() {
+        Ok("memory") => Arc::new(storage::MemoryStorage::new()),
+        _ => Arc::new(
+            storage::SqliteStorage::open(data_dir.join("answer-bot.sqlite3"))
+                .expect("Failed to open the database"),
+        ),
+    };
     let framework = poise::Framework::builder()
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 println!("Logged in as {}", _ready.user.name);
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 println!("Registered commands");
+                let http = ctx.clone();
+                let dispatch: games::Dispatch = Arc::new(move |outbox: utils::Outbox| {
+                    let http = http.clone();
+                    Box::pin(async move { outbox.send(&http).await })
+                });
                 Ok(Data {
-                    games: Default::default(),
+                    games: Arc::new(games::Games::new(storage.clone(), dispatch)?),
+                    storage,
                 })
             })
         })
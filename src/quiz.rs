@@ -0,0 +1,832 @@
+use std::path::PathBuf;
+
+use serenity::all::{Attachment, CreateAttachment, GuildId};
+
+use crate::config;
+use crate::engine;
+use crate::i18n;
+use crate::import;
+use crate::lint;
+use crate::settings::{self, Tone};
+use crate::templates::{self, Template};
+use crate::tr;
+use crate::utils;
+use crate::utils::create_embed;
+use crate::Context;
+use crate::Error;
+
+pub async fn autocomplete_pack(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    ctx.data()
+        .storage
+        .quiz_packs(guild)
+        .map(|packs| {
+            packs
+                .into_iter()
+                .filter(|p| p.starts_with(partial))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Manage the quiz packs of the guild
+#[poise::command(
+    slash_command,
+    subcommands(
+        "quiz_upload",
+        "quiz_download",
+        "quiz_list",
+        "quiz_delete",
+        "quiz_check",
+        "quiz_create",
+        "quiz_preview",
+        "question_cmd",
+        "answer_cmd"
+    ),
+    rename = "quiz",
+    guild_only
+)]
+pub async fn quiz_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Path of the json export of a quiz pack of the guild, or why its name can't be a file name
+fn pack_path(guild: GuildId, name: &str, lang: settings::Language) -> Result<PathBuf, String> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control)
+    {
+        return Err(tr!(
+            lang,
+            "A quiz pack name can't be empty, start with a dot or contain a slash",
+        ));
+    }
+    Ok(PathBuf::from(engine::QUIZ_DIR)
+        .join(guild.to_string())
+        .join(format!("{name}.json")))
+}
+
+/// Export a saved quiz pack to its json file in the quiz directory, or delete the file if
+/// `questions` is None. The packs are only read from the storage, so a failed export is
+/// logged without failing the command
+async fn export_pack(path: PathBuf, questions: Option<Vec<config::Question>>) {
+    let file = path.clone();
+    let export = tokio::task::spawn_blocking(move || -> Result<(), Error> {
+        let path = file;
+        let Some(questions) = questions else {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // The file is replaced at once, a pack is never left half written
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_vec_pretty(&questions)?)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(())
+    })
+    .await;
+    match export {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => println!("Error while exporting quiz pack {path:?}: {e:?}"),
+        Err(e) => println!("Error while exporting a quiz pack: {e:?}"),
+    }
+}
+
+/// Questions of an uploaded quiz pack, either a json file or a csv or tsv spreadsheet, or
+/// what is wrong with it
+async fn read_upload(
+    file: &Attachment,
+    lang: settings::Language,
+) -> Result<Result<Vec<config::Question>, String>, Error> {
+    let content = file.download().await?;
+    let name = file.filename.to_ascii_lowercase();
+    if name.ends_with(".csv") || name.ends_with(".tsv") {
+        return Ok(import::read_pack(&content).map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| e.describe(lang))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }));
+    }
+    Ok(serde_json::from_slice(&content)
+        .map_err(|e| tr!(lang, "The file isn't a valid quiz pack: {e}", e = e)))
+}
+
+/// Save a quiz pack from a json file or a spreadsheet, replacing the pack with the same name
+#[poise::command(slash_command, rename = "upload", guild_only)]
+pub async fn quiz_upload(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"] name: String,
+    #[description = "json file with the questions, or a csv or tsv spreadsheet"] file: Attachment,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().unwrap();
+    let path = match pack_path(guild, &name, i18n::lang(ctx)) {
+        Ok(path) => path,
+        Err(e) => return utils::send_error(ctx, e).await,
+    };
+    let questions = match read_upload(&file, i18n::lang(ctx)).await? {
+        Ok(questions) => questions,
+        Err(e) => return utils::send_error(ctx, e).await,
+    };
+    ctx.data()
+        .storage
+        .save_quiz_pack(guild, &name, &questions)?;
+    export_pack(path, Some(questions.clone())).await;
+    utils::send_reply(
+        ctx,
+        tr!(
+            i18n::lang(ctx),
+            "Saved quiz pack `{name}` with {count} questions",
+            name = name,
+            count = questions.len()
+        ),
+    )
+    .await
+}
+
+/// Get the json file of a quiz pack
+#[poise::command(slash_command, rename = "download", guild_only)]
+pub async fn quiz_download(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    name: String,
+) -> Result<(), Error> {
+    let Some(questions) = ctx
+        .data()
+        .storage
+        .quiz_pack(ctx.guild_id().unwrap(), &name)?
+    else {
+        utils::send_error(
+            ctx,
+            tr!(i18n::lang(ctx), "No quiz pack named `{name}`", name = name),
+        )
+        .await?;
+        return Ok(());
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(create_embed(
+                &settings::current(ctx),
+                Tone::Success,
+                "Success!",
+                tr!(
+                    i18n::lang(ctx),
+                    "Quiz pack `{name}` has {count} questions",
+                    name = name,
+                    count = questions.len()
+                ),
+            ))
+            .attachment(CreateAttachment::bytes(
+                serde_json::to_vec_pretty(&questions)?,
+                format!("{name}.json"),
+            )),
+    )
+    .await?;
+    Ok(())
+}
+
+/// List the quiz packs of the guild
+#[poise::command(slash_command, rename = "list", guild_only)]
+pub async fn quiz_list(ctx: Context<'_>) -> Result<(), Error> {
+    let packs = ctx.data().storage.quiz_packs(ctx.guild_id().unwrap())?;
+    if packs.is_empty() {
+        return utils::send_reply(ctx, "No quiz pack saved").await;
+    }
+    let msg = packs
+        .iter()
+        .map(|p| format!("-> `{p}`"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    utils::send_reply(ctx, msg).await
+}
+
+/// Delete a quiz pack
+#[poise::command(slash_command, rename = "delete", guild_only)]
+pub async fn quiz_delete(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().unwrap();
+    if !ctx.data().storage.delete_quiz_pack(guild, &name)? {
+        utils::send_error(
+            ctx,
+            tr!(i18n::lang(ctx), "No quiz pack named `{name}`", name = name),
+        )
+        .await?;
+        return Ok(());
+    }
+    // Packs saved before they were exported, or with a name that can't be a file, have none
+    if let Ok(path) = pack_path(guild, &name, i18n::lang(ctx)) {
+        export_pack(path, None).await;
+    }
+    utils::send_reply(
+        ctx,
+        tr!(i18n::lang(ctx), "Deleted quiz pack `{name}`", name = name),
+    )
+    .await
+}
+
+/// Look for mistakes in a quiz pack before playing it
+#[poise::command(slash_command, rename = "check", guild_only)]
+pub async fn quiz_check(
+    ctx: Context<'_>,
+    #[description = "quiz pack name, the default questions otherwise"]
+    #[autocomplete = "autocomplete_pack"]
+    name: Option<String>,
+    #[description = "json file or spreadsheet with the questions, to check a pack before uploading it"]
+    file: Option<Attachment>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let (questions, audio_dir) = match (name, file) {
+        (_, Some(file)) => match read_upload(&file, lang).await? {
+            Ok(questions) => (questions, None),
+            Err(e) => return utils::send_error(ctx, e).await,
+        },
+        (Some(name), None) => {
+            let Some(questions) = ctx
+                .data()
+                .storage
+                .quiz_pack(ctx.guild_id().unwrap(), &name)?
+            else {
+                let msg = tr!(lang, "No quiz pack named `{name}`", name = name);
+                return utils::send_error(ctx, msg).await;
+            };
+            (questions, None)
+        }
+        (None, None) => {
+            let path = std::path::Path::new(engine::DEFAULT_PACK);
+            (engine::read_pack(path)?, path.parent())
+        }
+    };
+    let issues = lint::check(&questions, audio_dir);
+    if issues.is_empty() {
+        let msg = tr!(
+            lang,
+            "No issue found in the {count} questions",
+            count = questions.len()
+        );
+        return utils::send_reply(ctx, msg).await;
+    }
+    let report = lint::report(&issues, lang)?;
+    if issues.iter().any(|i| i.severity() == lint::Severity::Error) {
+        utils::send_error(ctx, report).await
+    } else {
+        utils::send_reply(ctx, report).await
+    }
+}
+
+/// Change a quiz pack of the guild, replying with the message returned by `edit`. Edits of
+/// the same pack sent at the same time are made one after the other
+async fn edit_pack(
+    ctx: Context<'_>,
+    name: &str,
+    edit: impl FnOnce(&mut Vec<config::Question>) -> Result<String, String>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().unwrap();
+    let mut outcome = None;
+    let mut edited = None;
+    ctx.data().storage.update_quiz_pack(
+        guild,
+        name,
+        Box::new(|questions| {
+            let result = edit(questions);
+            let changed = result.is_ok();
+            if changed {
+                edited = Some(questions.clone());
+            }
+            outcome = Some(result);
+            changed
+        }),
+    )?;
+    // The export is written once the edit is saved, outside of the storage transaction
+    if let (Some(questions), Ok(path)) = (edited, pack_path(guild, name, i18n::lang(ctx))) {
+        export_pack(path, Some(questions)).await;
+    }
+    match outcome {
+        Some(Ok(msg)) => utils::send_reply(ctx, msg).await,
+        Some(Err(e)) => utils::send_error(ctx, e).await,
+        None => {
+            let msg = tr!(i18n::lang(ctx), "No quiz pack named `{name}`", name = name);
+            utils::send_error(ctx, msg).await
+        }
+    }
+}
+
+/// Position in the pack of the question numbered `number`, starting at 1
+fn question_index(
+    questions: &[config::Question],
+    number: usize,
+    lang: settings::Language,
+) -> Result<usize, String> {
+    if (1..=questions.len()).contains(&number) {
+        return Ok(number - 1);
+    }
+    Err(tr!(
+        lang,
+        "There is no question {number}, the pack has {count} questions",
+        number = number,
+        count = questions.len()
+    ))
+}
+
+/// Position in the question of the answer numbered `number`, starting at 1
+fn answer_index(
+    question: &config::Question,
+    number: usize,
+    lang: settings::Language,
+) -> Result<usize, String> {
+    if (1..=question.answer.len()).contains(&number) {
+        return Ok(number - 1);
+    }
+    Err(tr!(
+        lang,
+        "There is no answer {number}, the question has {count} answers",
+        number = number,
+        count = question.answer.len()
+    ))
+}
+
+/// An answer, with the other names it can be found by separated like in the spreadsheets
+fn new_answer(answer: String, aliases: Option<String>) -> config::Answer {
+    let aliases: Vec<String> = aliases
+        .iter()
+        .flat_map(|a| a.split(import::ALIAS_SEPARATOR))
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(String::from)
+        .collect();
+    if aliases.is_empty() {
+        return config::Answer::SingleAnswer(answer);
+    }
+    let names: Vec<String> = std::iter::once(answer).chain(aliases).collect();
+    let len = names.len();
+    config::Answer::MutlipleAnswer(names, len)
+}
+
+/// Insert a question without answers, as question `number` or after the last one
+fn add_question(
+    questions: &mut Vec<config::Question>,
+    name: String,
+    number: Option<usize>,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let number = number.unwrap_or(questions.len() + 1);
+    if number > questions.len() + 1 {
+        return Err(tr!(
+            lang,
+            "There is no question {number}, the pack has {count} questions",
+            number = number,
+            count = questions.len()
+        ));
+    }
+    let msg = tr!(
+        lang,
+        "Added question {number} `{name}`",
+        number = number,
+        name = name
+    );
+    questions.insert(
+        number - 1,
+        config::Question {
+            name,
+            answer: Vec::new(),
+            ..Default::default()
+        },
+    );
+    Ok(msg)
+}
+
+fn rename_question(
+    questions: &mut [config::Question],
+    number: usize,
+    name: String,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let index = question_index(questions, number, lang)?;
+    let msg = tr!(
+        lang,
+        "Renamed question {number} to `{name}`",
+        number = number,
+        name = name
+    );
+    questions[index].name = name;
+    Ok(msg)
+}
+
+fn remove_question(
+    questions: &mut Vec<config::Question>,
+    number: usize,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let index = question_index(questions, number, lang)?;
+    let question = questions.remove(index);
+    Ok(tr!(
+        lang,
+        "Removed question {number} `{name}`",
+        number = number,
+        name = question.name
+    ))
+}
+
+/// Move question `number` so that it becomes question `to`, shifting the ones in between
+fn move_question(
+    questions: &mut Vec<config::Question>,
+    number: usize,
+    to: usize,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let from = question_index(questions, number, lang)?;
+    let to = question_index(questions, to, lang)?;
+    let question = questions.remove(from);
+    let msg = tr!(
+        lang,
+        "Question `{name}` is now question {number}",
+        name = question.name,
+        number = to + 1
+    );
+    questions.insert(to, question);
+    Ok(msg)
+}
+
+fn add_answer(
+    questions: &mut [config::Question],
+    question: usize,
+    answer: config::Answer,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let index = question_index(questions, question, lang)?;
+    let question = &mut questions[index];
+    question.answer.push(answer);
+    Ok(tr!(
+        lang,
+        "Added answer {number} to `{question}`",
+        number = question.answer.len(),
+        question = question.name
+    ))
+}
+
+fn replace_answer(
+    questions: &mut [config::Question],
+    question: usize,
+    number: usize,
+    answer: config::Answer,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let index = question_index(questions, question, lang)?;
+    let question = &mut questions[index];
+    let answer_index = answer_index(question, number, lang)?;
+    question.answer[answer_index] = answer;
+    Ok(tr!(
+        lang,
+        "Replaced answer {number} of `{question}`",
+        number = number,
+        question = question.name
+    ))
+}
+
+fn remove_answer(
+    questions: &mut [config::Question],
+    question: usize,
+    number: usize,
+    lang: settings::Language,
+) -> Result<String, String> {
+    let index = question_index(questions, question, lang)?;
+    let question = &mut questions[index];
+    let answer_index = answer_index(question, number, lang)?;
+    question.answer.remove(answer_index);
+    Ok(tr!(
+        lang,
+        "Removed answer {number} of `{question}`",
+        number = number,
+        question = question.name
+    ))
+}
+
+/// Create an empty quiz pack to fill with `/quiz question add`
+#[poise::command(slash_command, rename = "create", guild_only)]
+pub async fn quiz_create(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"] name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().unwrap();
+    let storage = &ctx.data().storage;
+    if storage.quiz_pack(guild, &name)?.is_some() {
+        let msg = tr!(
+            i18n::lang(ctx),
+            "A quiz pack named `{name}` already exists",
+            name = name
+        );
+        return utils::send_error(ctx, msg).await;
+    }
+    let path = match pack_path(guild, &name, i18n::lang(ctx)) {
+        Ok(path) => path,
+        Err(e) => return utils::send_error(ctx, e).await,
+    };
+    storage.save_quiz_pack(guild, &name, &[])?;
+    export_pack(path, Some(Vec::new())).await;
+    utils::send_reply(
+        ctx,
+        tr!(i18n::lang(ctx), "Created quiz pack `{name}`", name = name),
+    )
+    .await
+}
+
+/// Show a question the way the admins will see it during the game
+#[poise::command(slash_command, rename = "preview", guild_only)]
+pub async fn quiz_preview(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    name: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    number: usize,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let Some(questions) = ctx
+        .data()
+        .storage
+        .quiz_pack(ctx.guild_id().unwrap(), &name)?
+    else {
+        let msg = tr!(lang, "No quiz pack named `{name}`", name = name);
+        return utils::send_error(ctx, msg).await;
+    };
+    let index = match question_index(&questions, number, lang) {
+        Ok(index) => index,
+        Err(e) => return utils::send_error(ctx, e).await,
+    };
+    let remaining = questions.len() - number;
+    let question = config::prepare_questions(vec![questions[index].clone()]);
+    let settings = settings::current(ctx);
+    let reply = poise::CreateReply {
+        embeds: templates::embed(
+            &settings,
+            Template::NextQuestion,
+            &templates::question_args(question.first(), remaining, settings.language),
+        ),
+        ..Default::default()
+    };
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Edit the questions of a quiz pack
+#[poise::command(
+    slash_command,
+    subcommands("question_add", "question_edit", "question_remove", "question_move"),
+    rename = "question",
+    guild_only
+)]
+pub async fn question_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Add a question to a quiz pack, its answers are added with `/quiz answer add`
+#[poise::command(slash_command, rename = "add", guild_only)]
+pub async fn question_add(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "name of the question"] name: String,
+    #[description = "number the question will have, the last one otherwise"]
+    #[min = 1]
+    number: Option<usize>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        add_question(questions, name, number, lang)
+    })
+    .await
+}
+
+/// Rename a question of a quiz pack
+#[poise::command(slash_command, rename = "edit", guild_only)]
+pub async fn question_edit(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    number: usize,
+    #[description = "new name of the question"] name: String,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        rename_question(questions, number, name, lang)
+    })
+    .await
+}
+
+/// Remove a question and its answers from a quiz pack
+#[poise::command(slash_command, rename = "remove", guild_only)]
+pub async fn question_remove(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    number: usize,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        remove_question(questions, number, lang)
+    })
+    .await
+}
+
+/// Move a question of a quiz pack, the questions in between are shifted
+#[poise::command(slash_command, rename = "move", guild_only)]
+pub async fn question_move(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    number: usize,
+    #[description = "number the question will have"]
+    #[min = 1]
+    to: usize,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        move_question(questions, number, to, lang)
+    })
+    .await
+}
+
+/// Edit the answers of a question of a quiz pack
+#[poise::command(
+    slash_command,
+    subcommands("answer_add", "answer_edit", "answer_remove"),
+    rename = "answer",
+    guild_only
+)]
+pub async fn answer_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Add an answer to a question of a quiz pack
+#[poise::command(slash_command, rename = "add", guild_only)]
+pub async fn answer_add(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    question: usize,
+    #[description = "answer to find"] answer: String,
+    #[description = "other names of the answer, separated by |"] aliases: Option<String>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        add_answer(questions, question, new_answer(answer, aliases), lang)
+    })
+    .await
+}
+
+/// Replace an answer of a question of a quiz pack
+#[poise::command(slash_command, rename = "edit", guild_only)]
+pub async fn answer_edit(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    question: usize,
+    #[description = "number of the answer"]
+    #[min = 1]
+    number: usize,
+    #[description = "answer to find"] answer: String,
+    #[description = "other names of the answer, separated by |"] aliases: Option<String>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        replace_answer(
+            questions,
+            question,
+            number,
+            new_answer(answer, aliases),
+            lang,
+        )
+    })
+    .await
+}
+
+/// Remove an answer from a question of a quiz pack
+#[poise::command(slash_command, rename = "remove", guild_only)]
+pub async fn answer_remove(
+    ctx: Context<'_>,
+    #[description = "quiz pack name"]
+    #[autocomplete = "autocomplete_pack"]
+    pack: String,
+    #[description = "number of the question"]
+    #[min = 1]
+    question: usize,
+    #[description = "number of the answer"]
+    #[min = 1]
+    number: usize,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    edit_pack(ctx, &pack, |questions| {
+        remove_answer(questions, question, number, lang)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Language;
+
+    const LANG: Language = Language::English;
+
+    #[test]
+    fn packs_are_files_of_the_guild_directory() {
+        let guild = GuildId::new(42);
+        let path = pack_path(guild, "90s hits", LANG).unwrap();
+        assert_eq!(path, PathBuf::from("blind_test/42/90s hits.json"));
+        for name in ["", ".hidden", "../music", "a/b", "a\\b"] {
+            assert!(pack_path(guild, name, LANG).is_err(), "{name}");
+        }
+    }
+
+    fn pack(names: &[&str]) -> Vec<config::Question> {
+        names
+            .iter()
+            .map(|n| config::Question {
+                name: (*n).to_owned(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn names(questions: &[config::Question]) -> Vec<&str> {
+        questions.iter().map(|q| q.name.as_str()).collect()
+    }
+
+    #[test]
+    fn questions_are_inserted_where_asked() {
+        let mut questions = pack(&["a", "c"]);
+        add_question(&mut questions, "b".to_owned(), Some(2), LANG).unwrap();
+        add_question(&mut questions, "d".to_owned(), None, LANG).unwrap();
+        assert_eq!(names(&questions), ["a", "b", "c", "d"]);
+        assert!(add_question(&mut questions, "f".to_owned(), Some(6), LANG).is_err());
+        assert_eq!(questions.len(), 4);
+    }
+
+    #[test]
+    fn questions_are_renamed_moved_and_removed() {
+        let mut questions = pack(&["a", "b", "c"]);
+        rename_question(&mut questions, 2, "B".to_owned(), LANG).unwrap();
+        move_question(&mut questions, 1, 3, LANG).unwrap();
+        assert_eq!(names(&questions), ["B", "c", "a"]);
+        remove_question(&mut questions, 2, LANG).unwrap();
+        assert_eq!(names(&questions), ["B", "a"]);
+        assert!(move_question(&mut questions, 1, 3, LANG).is_err());
+        assert!(remove_question(&mut questions, 0, LANG).is_err());
+        assert_eq!(names(&questions), ["B", "a"]);
+    }
+
+    #[test]
+    fn aliases_are_separated_like_in_spreadsheets() {
+        let answer = new_answer("queen".to_owned(), Some("freddie | , ".to_owned()));
+        let config::Answer::MutlipleAnswer(aliases, len) = answer else {
+            panic!("expected an answer with aliases");
+        };
+        assert_eq!(aliases, ["queen", "freddie", ","]);
+        assert_eq!(len, 3);
+        let answer = new_answer("queen".to_owned(), Some(" ".to_owned()));
+        assert!(matches!(answer, config::Answer::SingleAnswer(_)));
+    }
+
+    #[test]
+    fn answers_are_added_replaced_and_removed() {
+        let mut questions = pack(&["q"]);
+        let single = |name: &str| config::Answer::SingleAnswer(name.to_owned());
+        add_answer(&mut questions, 1, single("a"), LANG).unwrap();
+        add_answer(&mut questions, 1, single("b"), LANG).unwrap();
+        replace_answer(&mut questions, 1, 1, single("c"), LANG).unwrap();
+        remove_answer(&mut questions, 1, 2, LANG).unwrap();
+        let answers = &questions[0].answer;
+        assert!(matches!(&answers[..], [config::Answer::SingleAnswer(a)] if a == "c"));
+        assert!(add_answer(&mut questions, 2, single("d"), LANG).is_err());
+        assert!(remove_answer(&mut questions, 1, 2, LANG).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{CreateEmbed, Mentionable, Timestamp, UserId};
+
+use crate::config::{FoundAnswer, GuildConfig, Team};
+use crate::settings::Tone;
+use crate::utils::{create_embed, create_embeds, Outbox};
+use crate::{tr, Error};
+
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+fn place(i: usize) -> String {
+    MEDALS
+        .get(i)
+        .map(|m| (*m).to_owned())
+        .unwrap_or_else(|| format!("{}.", i + 1))
+}
+
+/// How to order teams or players that have the same number of points
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum TieBreaker {
+    /// Entries with the same points share the same rank
+    #[default]
+    #[name = "none"]
+    None,
+    /// The first to reach the final score is ranked higher
+    #[name = "first to reach the score"]
+    FirstToReach,
+    /// The one that found the most answers is ranked higher
+    #[name = "most answers found"]
+    MostAnswers,
+}
+
+/// An entry of a leaderboard with its competition rank, tied entries share the same rank
+pub struct Standing<T> {
+    pub rank: usize,
+    pub entry: T,
+    pub points: f64,
+    pub answers: usize,
+}
+
+struct Score {
+    /// Disqualified entries are ranked after every other one
+    disqualified: bool,
+    points: f64,
+    answers: usize,
+    /// When the entry reached its final score
+    reached: Option<Timestamp>,
+}
+
+fn compare(lhs: &Score, rhs: &Score, tie_breaker: TieBreaker) -> Ordering {
+    let by_points = lhs
+        .disqualified
+        .cmp(&rhs.disqualified)
+        .then(rhs.points.total_cmp(&lhs.points));
+    match tie_breaker {
+        TieBreaker::None => by_points,
+        TieBreaker::MostAnswers => by_points.then(rhs.answers.cmp(&lhs.answers)),
+        TieBreaker::FirstToReach => by_points.then(match (lhs.reached, rhs.reached) {
+            (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+            (lhs, rhs) => lhs.is_none().cmp(&rhs.is_none()),
+        }),
+    }
+}
+
+fn rank<T>(mut entries: Vec<(T, Score)>, tie_breaker: TieBreaker) -> Vec<Standing<T>> {
+    entries.sort_by(|lhs, rhs| compare(&lhs.1, &rhs.1, tie_breaker));
+    let mut standings: Vec<Standing<T>> = Vec::with_capacity(entries.len());
+    let mut previous: Option<Score> = None;
+    for (i, (entry, score)) in entries.into_iter().enumerate() {
+        let rank = match (&previous, standings.last()) {
+            (Some(prev), Some(last)) if compare(prev, &score, tie_breaker).is_eq() => last.rank,
+            _ => i + 1,
+        };
+        standings.push(Standing {
+            rank,
+            entry,
+            points: score.points,
+            answers: score.answers,
+        });
+        previous = Some(score);
+    }
+    standings
+}
+
+/// Teams ranked by their total of points
+pub fn team_standings(game: &GuildConfig, tie_breaker: TieBreaker) -> Vec<Standing<&Team>> {
+    let entries = game
+        .teams
+        .iter()
+        .map(|t| {
+            let found = game.found.iter().filter(|f| f.team == t.name);
+            let reached = found
+                .clone()
+                .map(|f| f.at)
+                .chain(
+                    game.adjustments
+                        .iter()
+                        .filter(|a| a.team == t.name)
+                        .map(|a| a.at),
+                )
+                .max();
+            let score = Score {
+                disqualified: t.disqualified.is_some(),
+                points: t.total_points,
+                answers: found.count(),
+                reached,
+            };
+            (t, score)
+        })
+        .collect();
+    rank(entries, tie_breaker)
+}
+
+/// Players of every team ranked by their points
+pub fn player_standings(
+    game: &GuildConfig,
+    tie_breaker: TieBreaker,
+) -> Vec<Standing<(UserId, &Team)>> {
+    let entries = game
+        .teams
+        .iter()
+        .flat_map(|t| {
+            t.leaderboard
+                .iter()
+                .map(move |(user, points)| (t, *user, *points))
+        })
+        .map(|(t, user, points)| {
+            let found = game
+                .found
+                .iter()
+                .filter(|f| f.user == user && f.team == t.name);
+            let reached = found
+                .clone()
+                .map(|f| f.at)
+                .chain(
+                    game.adjustments
+                        .iter()
+                        .filter(|a| a.user == Some(user) && a.team == t.name)
+                        .map(|a| a.at),
+                )
+                .max();
+            let score = Score {
+                disqualified: t.disqualified.is_some(),
+                points,
+                answers: found.count(),
+                reached,
+            };
+            ((user, t), score)
+        })
+        .collect();
+    rank(entries, tie_breaker)
+}
+
+fn podium(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let lang = game.settings.language;
+    let mut s = String::new();
+    for standing in team_standings(game, game.settings.tie_breaker) {
+        match &standing.entry.disqualified {
+            Some(reason) => writeln!(
+                &mut s,
+                "{}",
+                tr!(
+                    lang,
+                    "❌ `{team}` => {points}pts, disqualified: {reason}",
+                    team = standing.entry.name,
+                    points = standing.points,
+                    reason = reason
+                )
+            )?,
+            None => writeln!(
+                &mut s,
+                "{}",
+                tr!(
+                    lang,
+                    "{place} `{team}` => {points}pts",
+                    place = place(standing.rank - 1),
+                    team = standing.entry.name,
+                    points = standing.points
+                )
+            )?,
+        }
+    }
+    Ok(create_embeds(&game.settings, Tone::Highlight, "Podium", s))
+}
+
+fn players(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let mut s = String::new();
+    for standing in player_standings(game, game.settings.tie_breaker)
+        .into_iter()
+        .take(20)
+    {
+        let (user, team) = standing.entry;
+        writeln!(
+            &mut s,
+            "{}",
+            tr!(
+                game.settings.language,
+                "{place} {user} (`{team}`) => {points}pts, {answers} answers",
+                place = place(standing.rank - 1),
+                user = user.mention(),
+                team = team.name,
+                points = standing.points,
+                answers = standing.answers
+            )
+        )?;
+    }
+    Ok(create_embeds(&game.settings, Tone::Highlight, "Players", s))
+}
+
+/// Answer found the soonest after its question was opened. The messages sent before their
+/// question, then accepted by an admin, weren't answers to it
+fn fastest(found: &[FoundAnswer]) -> Option<&FoundAnswer> {
+    found
+        .iter()
+        .filter(|f| f.elapsed_ms >= 0)
+        .min_by_key(|f| f.elapsed_ms)
+}
+
+fn highlights(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let lang = game.settings.language;
+    let mut s = String::new();
+    let ranking = player_standings(game, game.settings.tie_breaker);
+    if let Some(mvp) = ranking.first() {
+        writeln!(
+            &mut s,
+            "{}",
+            tr!(
+                lang,
+                "MVP: {user} (`{team}`) with {points}pts",
+                user = mvp.entry.0.mention(),
+                team = mvp.entry.1.name,
+                points = mvp.points
+            )
+        )?;
+    }
+    if let Some(most) = ranking.iter().max_by_key(|p| p.answers) {
+        writeln!(
+            &mut s,
+            "{}",
+            tr!(
+                lang,
+                "Most answers found: {user} with {answers} answers",
+                user = most.entry.0.mention(),
+                answers = most.answers
+            )
+        )?;
+    }
+    if let Some(fastest) = fastest(&game.found) {
+        writeln!(
+            &mut s,
+            "{}",
+            tr!(
+                lang,
+                "Fastest answer: {user} found `{answer}` in {seconds}s",
+                user = fastest.user.mention(),
+                answer = fastest.answer,
+                seconds = format!("{:.1}", fastest.elapsed_ms as f64 / 1000.0)
+            )
+        )?;
+    }
+    Ok(create_embeds(
+        &game.settings,
+        Tone::Highlight,
+        "Highlights",
+        s,
+    ))
+}
+
+fn questions(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let mut by_question: BTreeMap<usize, Vec<&FoundAnswer>> = BTreeMap::new();
+    for found in &game.found {
+        by_question
+            .entry(found.question_number)
+            .or_default()
+            .push(found);
+    }
+    let mut s = String::new();
+    for (number, found) in by_question {
+        writeln!(&mut s, "**{number}. {}**", found[0].question)?;
+        for f in found {
+            writeln!(
+                &mut s,
+                "{}",
+                tr!(
+                    game.settings.language,
+                    "-> `{answer}` by {user} (`{team}`) after {seconds}s",
+                    answer = f.answer,
+                    user = f.user.mention(),
+                    team = f.team,
+                    seconds = format!("{:.1}", f.elapsed_ms as f64 / 1000.0)
+                )
+            )?;
+        }
+    }
+    Ok(create_embeds(
+        &game.settings,
+        Tone::Highlight,
+        "Questions",
+        s,
+    ))
+}
+
+fn adjustments(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let mut s = String::new();
+    for adj in &game.adjustments {
+        let team = match adj.user {
+            Some(user) => format!("`{}` ({})", adj.team, user.mention()),
+            None => format!("`{}`", adj.team),
+        };
+        writeln!(
+            &mut s,
+            "{}",
+            tr!(
+                game.settings.language,
+                "{amount} for team {team} by {admin}: {reason}",
+                amount = format!("{:+}", adj.amount),
+                team = team,
+                admin = adj.admin.mention(),
+                reason = adj.reason
+            )
+        )?;
+    }
+    Ok(create_embeds(
+        &game.settings,
+        Tone::Info,
+        "Points adjustments",
+        s,
+    ))
+}
+
+/// Every embed making up the end of game summary
+pub fn final_results(game: &GuildConfig) -> Result<Vec<CreateEmbed>, Error> {
+    let mut embeds = vec![create_embed(
+        &game.settings,
+        Tone::Success,
+        "Results",
+        "Here is how the game went !",
+    )];
+    embeds.extend(podium(game)?);
+    if !game.found.is_empty() {
+        embeds.extend(players(game)?);
+        embeds.extend(highlights(game)?);
+        embeds.extend(questions(game)?);
+    }
+    if !game.adjustments.is_empty() {
+        embeds.extend(adjustments(game)?);
+    }
+    Ok(embeds)
+}
+
+/// Send the end of game summary to every team and to the admins
+pub fn broadcast_results(outbox: &mut Outbox, game: &GuildConfig) {
+    let channels = game
+        .teams
+        .iter()
+        .map(|t| t.channel)
+        .chain([game.admin_channel])
+        .collect::<Vec<_>>();
+    match final_results(game) {
+        Ok(embeds) => outbox.broadcast(channels, embeds),
+        Err(e) => println!("Error while writing the results: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(points: f64, answers: usize, reached: Option<i64>) -> Score {
+        Score {
+            disqualified: false,
+            points,
+            answers,
+            reached: reached.map(|s| Timestamp::from_unix_timestamp(s).unwrap()),
+        }
+    }
+
+    fn ranks(
+        entries: Vec<(&'static str, Score)>,
+        tie_breaker: TieBreaker,
+    ) -> Vec<(usize, &'static str)> {
+        rank(entries, tie_breaker)
+            .into_iter()
+            .map(|s| (s.rank, s.entry))
+            .collect()
+    }
+
+    fn entries() -> Vec<(&'static str, Score)> {
+        vec![
+            ("late", score(3.0, 2, Some(20))),
+            ("low", score(1.0, 5, Some(1))),
+            ("early", score(3.0, 3, Some(10))),
+            ("top", score(4.0, 1, Some(30))),
+        ]
+    }
+
+    #[test]
+    fn tied_entries_share_their_rank() {
+        let ranked = ranks(entries(), TieBreaker::None);
+        assert_eq!(ranked[0], (1, "top"));
+        assert_eq!((ranked[1].0, ranked[2].0), (2, 2));
+        // The rank after a tie skips the shared places
+        assert_eq!(ranked[3], (4, "low"));
+    }
+
+    #[test]
+    fn tie_breakers_order_the_same_points() {
+        let expected = [(1, "top"), (2, "early"), (3, "late"), (4, "low")];
+        assert_eq!(ranks(entries(), TieBreaker::FirstToReach), expected);
+        assert_eq!(ranks(entries(), TieBreaker::MostAnswers), expected);
+        // Entries that never scored come after the ones that did
+        let never = vec![
+            ("none", score(0.0, 0, None)),
+            ("some", score(0.0, 0, Some(5))),
+        ];
+        assert_eq!(
+            ranks(never, TieBreaker::FirstToReach),
+            [(1, "some"), (2, "none")]
+        );
+    }
+
+    #[test]
+    fn disqualified_entries_come_last() {
+        let mut entries = entries();
+        entries[0].1.disqualified = true;
+        entries[3].1.disqualified = true;
+        let ranked = ranks(entries, TieBreaker::None);
+        assert_eq!(ranked[..2], [(1, "early"), (2, "low")]);
+        assert_eq!(ranked[2..], [(3, "top"), (4, "late")]);
+    }
+
+    #[test]
+    fn fastest_answer_ignores_the_messages_sent_before_the_question() {
+        let found = |answer: &str, elapsed_ms| FoundAnswer {
+            question_number: 1,
+            question: String::from("q"),
+            answer: answer.to_owned(),
+            team: String::from("team"),
+            user: UserId::new(1),
+            points: 1.0,
+            at: Timestamp::now(),
+            elapsed_ms,
+        };
+        let answers = [
+            found("slow", 9000),
+            found("accepted", -2000),
+            found("quick", 1500),
+        ];
+        assert_eq!(fastest(&answers).unwrap().answer, "quick");
+        assert!(fastest(&answers[1..2]).is_none());
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::BTreeMap;
+
+use poise::ChoiceParameter;
+use serde::{Deserialize, Serialize};
+use serenity::all::{Colour, Mentionable, Permissions, RoleId};
+
+use crate::i18n;
+use crate::results::TieBreaker;
+use crate::templates::Template;
+use crate::utils;
+use crate::{tr, Context, Error};
+
+/// Category of the commands every member can use, the others are reserved to the hosts
+pub const PLAYER: &str = "Player";
+
+/// Settings of a guild, kept between games. New games take a copy of them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Members with one of these roles can host games
+    pub admin_roles: Vec<RoleId>,
+    /// Members with these permissions can host games, nobody if empty
+    pub admin_permissions: Permissions,
+    pub language: Language,
+    /// Texts of the messages the guild rewrote, the default ones are used for the others
+    pub templates: BTreeMap<Template, String>,
+    pub theme: Theme,
+    pub scoring: Scoring,
+    /// Seconds given to the team that buzzed in the games played in buzzer mode
+    pub buzzer_window: Option<u64>,
+    /// Number of typos accepted in a guess
+    pub tolerance: usize,
+    /// How the results order the teams and players with the same points
+    pub tie_breaker: TieBreaker,
+    /// Seconds given to answer the questions without their own time limit, no limit if None
+    pub question_time_limit: Option<u64>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            admin_roles: Vec::new(),
+            admin_permissions: Permissions::MANAGE_GUILD,
+            language: Language::default(),
+            templates: BTreeMap::new(),
+            theme: Theme::default(),
+            scoring: Scoring::default(),
+            buzzer_window: None,
+            tolerance: 0,
+            tie_breaker: TieBreaker::default(),
+            question_time_limit: None,
+        }
+    }
+}
+
+impl GuildSettings {
+    /// The same settings in the language of a Discord locale, for the messages only one
+    /// user can see
+    pub fn for_locale(&self, locale: &str) -> Self {
+        let mut settings = self.clone();
+        if let Some(language) = Language::from_locale(locale) {
+            settings.language = language;
+        }
+        settings
+    }
+
+    /// How the guesses are matched and scored
+    pub fn rules(&self) -> crate::engine::Rules {
+        crate::engine::Rules {
+            tolerance: self.tolerance,
+            flat: matches!(self.scoring, Scoring::Flat),
+            time_limit: self.question_time_limit,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum Language {
+    #[default]
+    English,
+    #[name = "Français"]
+    French,
+}
+
+/// How many points an answer is worth
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum Scoring {
+    /// One point per answer, half a point for the other names of an answer already found
+    #[default]
+    #[name = "standard"]
+    Standard,
+    /// One point for every answer
+    #[name = "flat"]
+    Flat,
+}
+
+/// What an embed is about, deciding its colour
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum Tone {
+    #[name = "success"]
+    Success,
+    #[name = "error"]
+    Error,
+    #[name = "warning"]
+    Warning,
+    #[name = "highlight"]
+    Highlight,
+    #[name = "info"]
+    Info,
+}
+
+/// Look of the embeds sent by the bot
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub success: Colour,
+    pub error: Colour,
+    pub warning: Colour,
+    pub highlight: Colour,
+    pub info: Colour,
+    pub footer: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            success: Colour::from_rgb(0, 255, 0),
+            error: Colour::from_rgb(255, 0, 0),
+            warning: Colour::from_rgb(255, 165, 0),
+            highlight: Colour::from_rgb(255, 215, 0),
+            info: Colour::from_rgb(0, 0, 255),
+            footer: String::from("made by maiboyer with ❤️"),
+        }
+    }
+}
+
+impl Theme {
+    pub fn colour(&self, tone: Tone) -> Colour {
+        match tone {
+            Tone::Success => self.success,
+            Tone::Error => self.error,
+            Tone::Warning => self.warning,
+            Tone::Highlight => self.highlight,
+            Tone::Info => self.info,
+        }
+    }
+
+    fn colour_mut(&mut self, tone: Tone) -> &mut Colour {
+        match tone {
+            Tone::Success => &mut self.success,
+            Tone::Error => &mut self.error,
+            Tone::Warning => &mut self.warning,
+            Tone::Highlight => &mut self.highlight,
+            Tone::Info => &mut self.info,
+        }
+    }
+}
+
+/// Settings of the guild the command was sent in, the default ones outside of a guild.
+/// The storage keeps them in memory, so this doesn't query the database on every reply
+pub fn current(ctx: Context<'_>) -> GuildSettings {
+    ctx.guild_id()
+        .and_then(|g| ctx.data().storage.settings(g).ok())
+        .unwrap_or_default()
+}
+
+/// Discord permission that lets a member host games
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum AdminPermission {
+    #[name = "manage server"]
+    ManageServer,
+    #[name = "manage channels"]
+    ManageChannels,
+    #[name = "manage roles"]
+    ManageRoles,
+    #[name = "administrator"]
+    Administrator,
+    /// Only the admin roles can host games
+    #[name = "none"]
+    None,
+}
+
+impl From<AdminPermission> for Permissions {
+    fn from(value: AdminPermission) -> Self {
+        match value {
+            AdminPermission::ManageServer => Permissions::MANAGE_GUILD,
+            AdminPermission::ManageChannels => Permissions::MANAGE_CHANNELS,
+            AdminPermission::ManageRoles => Permissions::MANAGE_ROLES,
+            AdminPermission::Administrator => Permissions::ADMINISTRATOR,
+            AdminPermission::None => Permissions::empty(),
+        }
+    }
+}
+
+/// Whether the author of the command may host games in the guild
+pub async fn is_admin(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(false);
+    };
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+    let settings = ctx.data().storage.settings(guild)?;
+    if member
+        .roles
+        .iter()
+        .any(|r| settings.admin_roles.contains(r))
+    {
+        return Ok(true);
+    }
+    let permissions = member.permissions.unwrap_or_default();
+    Ok(permissions.administrator()
+        || (!settings.admin_permissions.is_empty()
+            && permissions.contains(settings.admin_permissions)))
+}
+
+/// Command checks: player commands are open to everyone, the others need a host
+pub async fn check(ctx: Context<'_>) -> Result<bool, Error> {
+    if ctx.command().category.as_deref() == Some(PLAYER) {
+        return Ok(true);
+    }
+    is_admin(ctx).await
+}
+
+/// Settings of the guild
+#[poise::command(
+    slash_command,
+    subcommands(
+        "config_show",
+        "config_admin_role",
+        "config_admin_permission",
+        "config_language",
+        "config_template",
+        "config_colour",
+        "config_footer",
+        "config_scoring",
+        "config_buzzer",
+        "config_tolerance",
+        "config_tie_breaker",
+        "config_time_limit"
+    ),
+    rename = "config",
+    guild_only
+)]
+pub async fn config_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Show the settings of the guild
+#[poise::command(slash_command, rename = "show", guild_only)]
+pub async fn config_show(ctx: Context<'_>) -> Result<(), Error> {
+    use std::fmt::Write;
+    let settings = ctx.data().storage.settings(ctx.guild_id().unwrap())?;
+    let lang = settings.language;
+    let roles = settings
+        .admin_roles
+        .iter()
+        .map(|r| r.mention().to_string())
+        .collect::<Vec<_>>();
+    let or_none = |s: String| {
+        if s.is_empty() {
+            i18n::translate(lang, "none").to_owned()
+        } else {
+            s
+        }
+    };
+    let theme = &settings.theme;
+    let lines = [
+        tr!(lang, "Admin roles: {roles}", roles = or_none(roles.join(", "))),
+        tr!(
+            lang,
+            "Admin permissions: {permissions}",
+            permissions = or_none(settings.admin_permissions.to_string())
+        ),
+        tr!(lang, "Language: {language}", language = lang.name()),
+        tr!(
+            lang,
+            "Rewritten messages: {templates}",
+            templates = or_none(
+                settings
+                    .templates
+                    .keys()
+                    .map(|t| i18n::translate(lang, t.name()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        ),
+        tr!(
+            lang,
+            "Colours: success #{success}, error #{error}, warning #{warning}, highlight #{highlight}, info #{info}",
+            success = theme.success.hex(),
+            error = theme.error.hex(),
+            warning = theme.warning.hex(),
+            highlight = theme.highlight.hex(),
+            info = theme.info.hex()
+        ),
+        tr!(lang, "Footer: {footer}", footer = or_none(theme.footer.clone())),
+        tr!(
+            lang,
+            "Scoring: {scoring}",
+            scoring = i18n::translate(lang, settings.scoring.name())
+        ),
+        tr!(
+            lang,
+            "Buzzer: {buzzer}",
+            buzzer = match settings.buzzer_window {
+                Some(w) => tr!(lang, "{window} seconds to answer", window = w),
+                None => i18n::translate(lang, "off").to_owned(),
+            }
+        ),
+        tr!(lang, "Typos accepted: {typos}", typos = settings.tolerance),
+        tr!(
+            lang,
+            "Tie-breaker: {tie_breaker}",
+            tie_breaker = i18n::translate(lang, settings.tie_breaker.name())
+        ),
+        tr!(
+            lang,
+            "Time limit: {time_limit}",
+            time_limit = match settings.question_time_limit {
+                Some(s) => tr!(lang, "{seconds} seconds per question", seconds = s),
+                None => i18n::translate(lang, "none").to_owned(),
+            }
+        ),
+    ];
+    let mut msg = String::new();
+    for line in lines {
+        writeln!(&mut msg, "{line}")?;
+    }
+    utils::send_reply(ctx, msg).await
+}
+
+/// Change the settings of the guild and save them, other changes made at the same time
+/// being applied one after the other
+async fn update(
+    ctx: Context<'_>,
+    change: impl FnOnce(&mut GuildSettings),
+    msg: impl AsRef<str>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().unwrap();
+    ctx.data()
+        .storage
+        .update_settings(guild, Box::new(change))?;
+    utils::send_reply(ctx, msg).await
+}
+
+/// Add or remove a role whose members can host games
+#[poise::command(slash_command, rename = "admin-role", guild_only)]
+pub async fn config_admin_role(
+    ctx: Context<'_>,
+    #[description = "role to add, or to remove if it already is an admin role"] role: RoleId,
+) -> Result<(), Error> {
+    let mut msg = String::new();
+    // Whether the role is removed or added depends on the settings being changed
+    ctx.data().storage.update_settings(
+        ctx.guild_id().unwrap(),
+        Box::new(|s| {
+            msg = if s.admin_roles.contains(&role) {
+                s.admin_roles.retain(|r| *r != role);
+                tr!(
+                    s.language,
+                    "{role} can't host games anymore",
+                    role = role.mention()
+                )
+            } else {
+                s.admin_roles.push(role);
+                tr!(
+                    s.language,
+                    "{role} can now host games",
+                    role = role.mention()
+                )
+            };
+        }),
+    )?;
+    utils::send_reply(ctx, msg).await
+}
+
+/// Choose the Discord permission that lets a member host games
+#[poise::command(slash_command, rename = "admin-permission", guild_only)]
+pub async fn config_admin_permission(
+    ctx: Context<'_>,
+    #[description = "permission needed to host games"] permission: AdminPermission,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let msg = tr!(
+        lang,
+        "Members with the `{permission}` permission can host games",
+        permission = i18n::translate(lang, permission.name())
+    );
+    update(ctx, |s| s.admin_permissions = permission.into(), msg).await
+}
+
+/// Choose the language of the bot
+#[poise::command(slash_command, rename = "language", guild_only)]
+pub async fn config_language(
+    ctx: Context<'_>,
+    #[description = "language of the messages"] language: Language,
+) -> Result<(), Error> {
+    let msg = tr!(
+        language,
+        "The bot now speaks {language}",
+        language = language.name()
+    );
+    update(ctx, |s| s.language = language, msg).await
+}
+
+/// Rewrite a message of the bot, `{placeholders}` are replaced by the game values
+#[poise::command(slash_command, rename = "template", guild_only)]
+pub async fn config_template(
+    ctx: Context<'_>,
+    #[description = "message to rewrite"] template: Template,
+    #[description = "new text, the default one if not given"] text: Option<String>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    // Slash command options can't hold line breaks
+    let text = text.map(|t| t.replace("\\n", "\n"));
+    let placeholders = template
+        .placeholders()
+        .iter()
+        .map(|p| format!("`{{{p}}}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Some(unknown) = text
+        .as_deref()
+        .map(|t| template.unknown_placeholders(t))
+        .and_then(|u| u.first().cloned())
+    {
+        utils::send_error(
+            ctx,
+            tr!(
+                lang,
+                "The `{template}` message has no `{{unknown}}` placeholder, it can use {placeholders}",
+                template = i18n::translate(lang, template.name()),
+                unknown = unknown,
+                placeholders = placeholders
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    let msg = tr!(
+        lang,
+        "Changed the `{template}` message",
+        template = i18n::translate(lang, template.name())
+    );
+    update(
+        ctx,
+        |s| match text {
+            Some(text) => {
+                s.templates.insert(template, text);
+            }
+            None => {
+                s.templates.remove(&template);
+            }
+        },
+        msg,
+    )
+    .await
+}
+
+/// Change the colour of an embed
+#[poise::command(slash_command, rename = "colour", guild_only)]
+pub async fn config_colour(
+    ctx: Context<'_>,
+    #[description = "kind of embed"] tone: Tone,
+    #[description = "colour like #ff8800"] colour: String,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let Ok(value) = u32::from_str_radix(colour.trim_start_matches('#'), 16) else {
+        utils::send_error(
+            ctx,
+            tr!(
+                lang,
+                "`{colour}` isn't a colour like #ff8800",
+                colour = colour
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    let msg = tr!(
+        lang,
+        "Changed the {tone} colour",
+        tone = i18n::translate(lang, tone.name())
+    );
+    update(ctx, |s| *s.theme.colour_mut(tone) = Colour::new(value), msg).await
+}
+
+/// Change the footer of the embeds
+#[poise::command(slash_command, rename = "footer", guild_only)]
+pub async fn config_footer(
+    ctx: Context<'_>,
+    #[description = "footer, none if not given"] footer: Option<String>,
+) -> Result<(), Error> {
+    let footer = footer.unwrap_or_default();
+    update(ctx, |s| s.theme.footer = footer, "Changed the footer").await
+}
+
+/// Choose how many points the answers are worth in new games
+#[poise::command(slash_command, rename = "scoring", guild_only)]
+pub async fn config_scoring(
+    ctx: Context<'_>,
+    #[description = "scoring mode"] scoring: Scoring,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let msg = tr!(
+        lang,
+        "New games use the {scoring} scoring",
+        scoring = i18n::translate(lang, scoring.name())
+    );
+    update(ctx, |s| s.scoring = scoring, msg).await
+}
+
+/// Play new games in buzzer mode by default
+#[poise::command(slash_command, rename = "buzzer", guild_only)]
+pub async fn config_buzzer(
+    ctx: Context<'_>,
+    #[description = "seconds given to the team that buzzed, no buzzer if not given"]
+    #[min = 1]
+    window: Option<u64>,
+) -> Result<(), Error> {
+    let msg = match window {
+        Some(w) => tr!(
+            i18n::lang(ctx),
+            "New games use a buzzer with {window} seconds to answer",
+            window = w
+        ),
+        None => String::from("New games don't use a buzzer"),
+    };
+    update(ctx, |s| s.buzzer_window = window, msg).await
+}
+
+/// Accept guesses with a few typos in new games
+#[poise::command(slash_command, rename = "tolerance", guild_only)]
+pub async fn config_tolerance(
+    ctx: Context<'_>,
+    #[description = "number of typos accepted"]
+    #[max = 5]
+    typos: usize,
+) -> Result<(), Error> {
+    let msg = tr!(
+        i18n::lang(ctx),
+        "New games accept up to {typos} typos",
+        typos = typos
+    );
+    update(ctx, |s| s.tolerance = typos, msg).await
+}
+
+/// Choose how the results of new games order the teams and players with the same points
+#[poise::command(slash_command, rename = "tie-breaker", guild_only)]
+pub async fn config_tie_breaker(
+    ctx: Context<'_>,
+    #[description = "how to order teams and players with the same points"] tie_breaker: TieBreaker,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let msg = tr!(
+        lang,
+        "New games break ties by {tie_breaker}",
+        tie_breaker = i18n::translate(lang, tie_breaker.name())
+    );
+    update(ctx, |s| s.tie_breaker = tie_breaker, msg).await
+}
+
+/// Close the questions of new games after a time, unless the quiz pack gives them their own
+#[poise::command(slash_command, rename = "time-limit", guild_only)]
+pub async fn config_time_limit(
+    ctx: Context<'_>,
+    #[description = "seconds given to answer each question, no limit if not given"]
+    #[min = 1]
+    seconds: Option<u64>,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let msg = match seconds {
+        Some(s) => tr!(
+            lang,
+            "New games give {seconds} seconds to answer each question",
+            seconds = s
+        ),
+        None => i18n::translate(lang, "New games don't limit the time to answer").to_owned(),
+    };
+    update(ctx, |s| s.question_time_limit = seconds, msg).await
+}
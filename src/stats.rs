@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{Mentionable, Timestamp, UserId};
+
+use crate::config::GuildConfig;
+use crate::i18n;
+use crate::results::player_standings;
+use crate::settings::Language;
+use crate::utils;
+use crate::{tr, Context, Error};
+
+/// How a player did in a finished game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub user: UserId,
+    pub team: String,
+    pub points: f64,
+    pub answers: usize,
+    pub won: bool,
+}
+
+/// A finished game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub game_id: u64,
+    pub ended_at: Timestamp,
+    pub players: Vec<PlayerRecord>,
+}
+
+impl GameRecord {
+    pub fn new(game: &GuildConfig) -> Self {
+        let best = game
+            .teams
+            .iter()
+            .filter(|t| t.disqualified.is_none())
+            .map(|t| t.total_points)
+            .max_by(f64::total_cmp);
+        let winners: Vec<_> = game
+            .teams
+            .iter()
+            .filter(|t| t.disqualified.is_none() && Some(t.total_points) == best)
+            .map(|t| t.name.clone())
+            .collect();
+        Self {
+            game_id: game.id,
+            ended_at: Timestamp::now(),
+            players: player_standings(game, game.settings.tie_breaker)
+                .into_iter()
+                .map(|p| PlayerRecord {
+                    user: p.entry.0,
+                    won: winners.contains(&p.entry.1.name),
+                    team: p.entry.1.name.clone(),
+                    points: p.points,
+                    answers: p.answers,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Season {
+    pub name: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+impl Season {
+    pub fn contains(&self, at: Timestamp) -> bool {
+        self.start <= at && at <= self.end
+    }
+}
+
+/// Statistics of a player over several games
+#[derive(Clone, Debug, Default)]
+pub struct PlayerStats {
+    pub games: usize,
+    pub answers: usize,
+    pub points: f64,
+    pub wins: usize,
+}
+
+/// Everything remembered about the games of a guild
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GuildStats {
+    pub games: Vec<GameRecord>,
+    pub seasons: Vec<Season>,
+}
+
+impl GuildStats {
+    fn games_in<'a>(&'a self, season: Option<&'a Season>) -> impl Iterator<Item = &'a GameRecord> {
+        self.games
+            .iter()
+            .filter(move |g| season.map(|s| s.contains(g.ended_at)).unwrap_or(true))
+    }
+
+    pub fn season(&self, name: &str) -> Option<&Season> {
+        self.seasons.iter().find(|s| s.name == name)
+    }
+
+    pub fn current_season(&self) -> Option<&Season> {
+        let now = Timestamp::now();
+        self.seasons.iter().find(|s| s.contains(now))
+    }
+
+    /// Every player that played during the season, best first
+    pub fn leaderboard(&self, season: Option<&Season>) -> Vec<(UserId, PlayerStats)> {
+        let mut players: HashMap<UserId, PlayerStats> = HashMap::new();
+        for record in self.games_in(season).flat_map(|g| g.players.iter()) {
+            let stats = players.entry(record.user).or_default();
+            stats.games += 1;
+            stats.answers += record.answers;
+            stats.points += record.points;
+            stats.wins += usize::from(record.won);
+        }
+        let mut players: Vec<_> = players.into_iter().collect();
+        players.sort_by(|lhs, rhs| {
+            rhs.1
+                .points
+                .total_cmp(&lhs.1.points)
+                .then(rhs.1.wins.cmp(&lhs.1.wins))
+        });
+        players
+    }
+
+    pub fn player(&self, user: UserId, season: Option<&Season>) -> PlayerStats {
+        self.leaderboard(season)
+            .into_iter()
+            .find(|(u, _)| *u == user)
+            .map(|(_, s)| s)
+            .unwrap_or_default()
+    }
+}
+
+fn parse_date(lang: Language, date: &str, end_of_day: bool) -> Result<Timestamp, Error> {
+    let time = if end_of_day { "23:59:59" } else { "00:00:00" };
+    Timestamp::parse(&format!("{date}T{time}Z"))
+        .map_err(|_| tr!(lang, "`{date}` isn't a date like 2024-09-01", date = date).into())
+}
+
+fn format_stats(lang: Language, stats: &PlayerStats) -> String {
+    tr!(
+        lang,
+        "Games played: {games}\nWins: {wins}\nAnswers found: {answers}\nPoints: {points}",
+        games = stats.games,
+        wins = stats.wins,
+        answers = stats.answers,
+        points = stats.points
+    )
+}
+
+async fn autocomplete_season(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    ctx.data()
+        .storage
+        .guild_stats(guild)
+        .map(|s| {
+            s.seasons
+                .into_iter()
+                .map(|s| s.name)
+                .filter(|n| n.starts_with(partial))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn show_stats(ctx: Context<'_>, user: UserId, season: Option<String>) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let stats = ctx.data().storage.guild_stats(ctx.guild_id().unwrap())?;
+    let season = match &season {
+        Some(name) => match stats.season(name) {
+            Some(s) => Some(s),
+            None => {
+                utils::send_error(ctx, tr!(lang, "No season named `{name}`", name = name)).await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let player = stats.player(user, season);
+    utils::send_reply(
+        ctx,
+        match season {
+            Some(season) => tr!(
+                lang,
+                "Stats of {user} for season `{season}`\n\n{stats}",
+                user = user.mention(),
+                season = season.name,
+                stats = format_stats(lang, &player)
+            ),
+            None => tr!(
+                lang,
+                "Stats of {user}\n\n{stats}",
+                user = user.mention(),
+                stats = format_stats(lang, &player)
+            ),
+        },
+    )
+    .await
+}
+
+/// Statistics of the players over every game
+#[poise::command(
+    slash_command,
+    subcommands("stats_me", "stats_user"),
+    rename = "stats",
+    guild_only
+)]
+pub async fn stats_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Show your own statistics
+#[poise::command(slash_command, rename = "me", guild_only, category = "Player")]
+pub async fn stats_me(
+    ctx: Context<'_>,
+    #[description = "only count the games of this season"]
+    #[autocomplete = "autocomplete_season"]
+    season: Option<String>,
+) -> Result<(), Error> {
+    show_stats(ctx, ctx.author().id, season).await
+}
+
+/// Show the statistics of a player
+#[poise::command(slash_command, rename = "user", guild_only, category = "Player")]
+pub async fn stats_user(
+    ctx: Context<'_>,
+    #[description = "player to show"] user: serenity::all::User,
+    #[description = "only count the games of this season"]
+    #[autocomplete = "autocomplete_season"]
+    season: Option<String>,
+) -> Result<(), Error> {
+    show_stats(ctx, user.id, season).await
+}
+
+/// Rankings over several games
+#[poise::command(
+    slash_command,
+    subcommands("leaderboard_season"),
+    rename = "leaderboard",
+    guild_only
+)]
+pub async fn leaderboard_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Show the ranking of the players over a season
+#[poise::command(slash_command, rename = "season", guild_only, category = "Player")]
+pub async fn leaderboard_season(
+    ctx: Context<'_>,
+    #[description = "season to show, the current one by default"]
+    #[autocomplete = "autocomplete_season"]
+    season: Option<String>,
+) -> Result<(), Error> {
+    let stats = ctx.data().storage.guild_stats(ctx.guild_id().unwrap())?;
+    let season = match &season {
+        Some(name) => stats.season(name),
+        None => stats.current_season(),
+    };
+    let Some(season) = season else {
+        utils::send_error(ctx, "No such season").await?;
+        return Ok(());
+    };
+    use std::fmt::Write;
+    let lang = i18n::lang(ctx);
+    let mut msg = tr!(lang, "Season `{season}`", season = season.name);
+    msg.push_str("\n\n");
+    for (i, (user, player)) in stats
+        .leaderboard(Some(season))
+        .into_iter()
+        .take(20)
+        .enumerate()
+    {
+        writeln!(
+            &mut msg,
+            "{}",
+            tr!(
+                lang,
+                "{rank}) {user} => {points}pts, {wins} wins in {games} games",
+                rank = i + 1,
+                user = user.mention(),
+                points = player.points,
+                wins = player.wins,
+                games = player.games
+            )
+        )?;
+    }
+    utils::send_reply(ctx, msg).await
+}
+
+/// Manage the seasons of the guild
+#[poise::command(
+    slash_command,
+    subcommands("season_add", "season_remove", "season_list"),
+    rename = "season",
+    guild_only
+)]
+pub async fn season_cmd(_ctx: Context<'_>) -> Result<(), Error> {
+    unreachable!()
+}
+
+/// Define a new season
+#[poise::command(slash_command, rename = "add", guild_only)]
+pub async fn season_add(
+    ctx: Context<'_>,
+    #[description = "season name"] name: String,
+    #[description = "first day of the season, like 2024-09-01"] start: String,
+    #[description = "last day of the season, like 2025-06-30"] end: String,
+) -> Result<(), Error> {
+    let lang = i18n::lang(ctx);
+    let (start, end) = match (
+        parse_date(lang, &start, false),
+        parse_date(lang, &end, true),
+    ) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(e), _) | (_, Err(e)) => {
+            utils::send_error(ctx, e.to_string()).await?;
+            return Ok(());
+        }
+    };
+    if end < start {
+        utils::send_error(ctx, "The season ends before it starts").await?;
+        return Ok(());
+    }
+    let added = ctx.data().storage.add_season(
+        ctx.guild_id().unwrap(),
+        &Season {
+            name: name.clone(),
+            start,
+            end,
+        },
+    )?;
+    if !added {
+        utils::send_error(ctx, "A season already exists with that name !").await?;
+        return Ok(());
+    }
+    utils::send_reply(ctx, tr!(lang, "Created season `{name}`", name = name)).await
+}
+
+/// Remove a season, the games played during it are kept
+#[poise::command(slash_command, rename = "remove", guild_only)]
+pub async fn season_remove(
+    ctx: Context<'_>,
+    #[description = "season name"]
+    #[autocomplete = "autocomplete_season"]
+    name: String,
+) -> Result<(), Error> {
+    let removed = ctx
+        .data()
+        .storage
+        .remove_season(ctx.guild_id().unwrap(), &name)?;
+    if !removed {
+        utils::send_error(ctx, "No season exists with this name !").await?;
+        return Ok(());
+    }
+    utils::send_reply(
+        ctx,
+        tr!(i18n::lang(ctx), "Removed season `{name}`", name = name),
+    )
+    .await
+}
+
+/// List the seasons of the guild
+#[poise::command(slash_command, rename = "list", guild_only, category = "Player")]
+pub async fn season_list(ctx: Context<'_>) -> Result<(), Error> {
+    let stats = ctx.data().storage.guild_stats(ctx.guild_id().unwrap())?;
+    use std::fmt::Write;
+    let mut msg = String::new();
+    for season in &stats.seasons {
+        writeln!(
+            &mut msg,
+            "`{}`: {} -> {}",
+            season.name,
+            season.start.date_naive(),
+            season.end.date_naive()
+        )?;
+    }
+    if msg.is_empty() {
+        msg.push_str(i18n::translate(i18n::lang(ctx), "No season defined"));
+    }
+    utils::send_reply(ctx, msg).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> Timestamp {
+        Timestamp::parse(&format!("{date}T12:00:00Z")).unwrap()
+    }
+
+    fn record(user: u64, points: f64, won: bool) -> PlayerRecord {
+        PlayerRecord {
+            user: UserId::new(user),
+            team: String::from("team"),
+            points,
+            answers: points as usize,
+            won,
+        }
+    }
+
+    fn game(date: &str, players: Vec<PlayerRecord>) -> GameRecord {
+        GameRecord {
+            game_id: 1,
+            ended_at: at(date),
+            players,
+        }
+    }
+
+    fn summer() -> Season {
+        Season {
+            name: String::from("summer"),
+            start: parse_date(Language::English, "2024-06-01", false).unwrap(),
+            end: parse_date(Language::English, "2024-08-31", true).unwrap(),
+        }
+    }
+
+    #[test]
+    fn seasons_contain_their_first_and_last_days() {
+        let season = summer();
+        assert!(season.contains(at("2024-06-01")));
+        assert!(season.contains(at("2024-08-31")));
+        assert!(!season.contains(at("2024-05-31")));
+        assert!(!season.contains(at("2024-09-01")));
+    }
+
+    #[test]
+    fn leaderboard_adds_up_the_games_of_the_season() {
+        let stats = GuildStats {
+            games: vec![
+                game("2024-05-20", vec![record(1, 10.0, true)]),
+                game(
+                    "2024-06-10",
+                    vec![record(1, 2.0, false), record(2, 3.0, true)],
+                ),
+                game(
+                    "2024-07-10",
+                    vec![record(1, 3.0, true), record(3, 5.0, false)],
+                ),
+            ],
+            seasons: vec![summer()],
+        };
+        let users = |leaderboard: &[(UserId, PlayerStats)]| {
+            leaderboard.iter().map(|(u, _)| u.get()).collect::<Vec<_>>()
+        };
+
+        let all_time = stats.leaderboard(None);
+        assert_eq!(users(&all_time), [1, 3, 2]);
+        assert_eq!(all_time[0].1.games, 3);
+        assert_eq!(all_time[0].1.wins, 2);
+        assert_eq!(all_time[0].1.points, 15.0);
+
+        // The games of the season only, the ties being broken by the wins
+        let season = stats.season("summer");
+        let leaderboard = stats.leaderboard(season);
+        assert_eq!(users(&leaderboard), [1, 3, 2]);
+        assert_eq!(leaderboard[0].1.points, 5.0);
+        assert_eq!(leaderboard[0].1.wins, 1);
+        assert_eq!(stats.player(UserId::new(2), season).games, 1);
+        assert_eq!(stats.player(UserId::new(4), season).games, 0);
+    }
+}
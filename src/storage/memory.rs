@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serenity::all::GuildId;
+
+use super::{QuizEdit, SettingsEdit, Storage};
+use crate::config::{GuildConfig, Question};
+use crate::events::LoggedEvent;
+use crate::settings::GuildSettings;
+use crate::stats::{GameRecord, GuildStats, Season};
+use crate::Error;
+
+#[derive(Default)]
+struct Inner {
+    games: HashMap<GuildId, GuildConfig>,
+    events: HashMap<(GuildId, u64), Vec<LoggedEvent>>,
+    packs: HashMap<GuildId, HashMap<String, Vec<Question>>>,
+    stats: HashMap<GuildId, GuildStats>,
+    settings: HashMap<GuildId, GuildSettings>,
+}
+
+/// Storage that forgets everything when the bot stops, for tests and trying the bot out
+#[derive(Default)]
+pub struct MemoryStorage {
+    inner: Mutex<Inner>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn games(&self) -> Result<Vec<(GuildId, GuildConfig)>, Error> {
+        let inner = self.lock();
+        Ok(inner
+            .games
+            .iter()
+            .map(|(g, c)| {
+                let mut game = c.clone();
+                game.events = inner.events.get(&(*g, c.id)).cloned().unwrap_or_default();
+                (*g, game)
+            })
+            .collect())
+    }
+
+    fn game(&self, guild: GuildId) -> Result<Option<GuildConfig>, Error> {
+        let inner = self.lock();
+        Ok(inner.games.get(&guild).map(|c| {
+            let mut game = c.clone();
+            game.events = inner
+                .events
+                .get(&(guild, c.id))
+                .cloned()
+                .unwrap_or_default();
+            game
+        }))
+    }
+
+    fn save_game(&self, guild: GuildId, game: &GuildConfig) -> Result<(), Error> {
+        let mut inner = self.lock();
+        let events = inner.events.entry((guild, game.id)).or_default();
+        let saved = events.len();
+        events.extend(game.events.iter().skip(saved).cloned());
+        inner.games.insert(
+            guild,
+            GuildConfig {
+                events: Vec::new(),
+                ..game.clone()
+            },
+        );
+        Ok(())
+    }
+
+    fn delete_game(&self, guild: GuildId) -> Result<(), Error> {
+        self.lock().games.remove(&guild);
+        Ok(())
+    }
+
+    fn events(&self, guild: GuildId, game_id: u64) -> Result<Vec<LoggedEvent>, Error> {
+        Ok(self
+            .lock()
+            .events
+            .get(&(guild, game_id))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn quiz_packs(&self, guild: GuildId) -> Result<Vec<String>, Error> {
+        let mut names: Vec<_> = self
+            .lock()
+            .packs
+            .get(&guild)
+            .map(|p| p.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        Ok(names)
+    }
+
+    fn quiz_pack(&self, guild: GuildId, name: &str) -> Result<Option<Vec<Question>>, Error> {
+        Ok(self
+            .lock()
+            .packs
+            .get(&guild)
+            .and_then(|p| p.get(name))
+            .cloned())
+    }
+
+    fn save_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        questions: &[Question],
+    ) -> Result<(), Error> {
+        self.lock()
+            .packs
+            .entry(guild)
+            .or_default()
+            .insert(name.to_owned(), questions.to_vec());
+        Ok(())
+    }
+
+    fn update_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        edit: QuizEdit<'_>,
+    ) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        let Some(questions) = inner.packs.get_mut(&guild).and_then(|p| p.get_mut(name)) else {
+            return Ok(false);
+        };
+        let mut edited = questions.clone();
+        if edit(&mut edited) {
+            *questions = edited;
+        }
+        Ok(true)
+    }
+
+    fn delete_quiz_pack(&self, guild: GuildId, name: &str) -> Result<bool, Error> {
+        Ok(self
+            .lock()
+            .packs
+            .get_mut(&guild)
+            .and_then(|p| p.remove(name))
+            .is_some())
+    }
+
+    fn guild_stats(&self, guild: GuildId) -> Result<GuildStats, Error> {
+        Ok(self.lock().stats.get(&guild).cloned().unwrap_or_default())
+    }
+
+    fn record_game(&self, guild: GuildId, record: &GameRecord) -> Result<(), Error> {
+        self.lock()
+            .stats
+            .entry(guild)
+            .or_default()
+            .games
+            .push(record.clone());
+        Ok(())
+    }
+
+    fn add_season(&self, guild: GuildId, season: &Season) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        let stats = inner.stats.entry(guild).or_default();
+        if stats.season(&season.name).is_some() {
+            return Ok(false);
+        }
+        stats.seasons.push(season.clone());
+        Ok(true)
+    }
+
+    fn remove_season(&self, guild: GuildId, name: &str) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        let Some(stats) = inner.stats.get_mut(&guild) else {
+            return Ok(false);
+        };
+        let len = stats.seasons.len();
+        stats.seasons.retain(|s| s.name != name);
+        Ok(len != stats.seasons.len())
+    }
+
+    fn settings(&self, guild: GuildId) -> Result<GuildSettings, Error> {
+        Ok(self
+            .lock()
+            .settings
+            .get(&guild)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn update_settings(&self, guild: GuildId, edit: SettingsEdit<'_>) -> Result<(), Error> {
+        edit(self.lock().settings.entry(guild).or_default());
+        Ok(())
+    }
+}
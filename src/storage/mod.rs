@@ -0,0 +1,304 @@
+mod memory;
+mod sqlite;
+
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+
+use serenity::all::GuildId;
+
+use crate::config::{GuildConfig, Question};
+use crate::events::LoggedEvent;
+use crate::settings::GuildSettings;
+use crate::stats::{GameRecord, GuildStats, Season};
+use crate::Error;
+
+/// Change made to the questions of a quiz pack, returning whether the pack must be saved
+pub type QuizEdit<'a> = Box<dyn FnOnce(&mut Vec<Question>) -> bool + 'a>;
+
+/// Change made to the settings of a guild
+pub type SettingsEdit<'a> = Box<dyn FnOnce(&mut GuildSettings) + 'a>;
+
+/// Backend keeping everything that must survive a restart of the bot
+pub trait Storage: Send + Sync {
+    /// Every game that wasn't deleted with its event log, to be restored when the bot starts
+    fn games(&self) -> Result<Vec<(GuildId, GuildConfig)>, Error>;
+    /// Game of the guild with its event log, if it wasn't deleted
+    fn game(&self, guild: GuildId) -> Result<Option<GuildConfig>, Error>;
+    /// Save the current state of a game, appending the events logged since it was last saved
+    fn save_game(&self, guild: GuildId, game: &GuildConfig) -> Result<(), Error>;
+    /// Forget a game, its event log is kept
+    fn delete_game(&self, guild: GuildId) -> Result<(), Error>;
+    /// Event log of a game of the guild, even if it was deleted
+    fn events(&self, guild: GuildId, game_id: u64) -> Result<Vec<LoggedEvent>, Error>;
+
+    fn quiz_packs(&self, guild: GuildId) -> Result<Vec<String>, Error>;
+    /// Questions of a quiz pack, in the order they are asked
+    fn quiz_pack(&self, guild: GuildId, name: &str) -> Result<Option<Vec<Question>>, Error>;
+    fn save_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        questions: &[Question],
+    ) -> Result<(), Error>;
+    /// Change a quiz pack, no other change of the storage happening in between. The pack is
+    /// saved if `edit` returns true. Returns false if no pack had this name
+    fn update_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        edit: QuizEdit<'_>,
+    ) -> Result<bool, Error>;
+    /// Returns false if no pack had this name
+    fn delete_quiz_pack(&self, guild: GuildId, name: &str) -> Result<bool, Error>;
+
+    fn guild_stats(&self, guild: GuildId) -> Result<GuildStats, Error>;
+    fn record_game(&self, guild: GuildId, record: &GameRecord) -> Result<(), Error>;
+    /// Returns false if a season already had this name
+    fn add_season(&self, guild: GuildId, season: &Season) -> Result<bool, Error>;
+    /// Returns false if no season had this name
+    fn remove_season(&self, guild: GuildId, name: &str) -> Result<bool, Error>;
+
+    /// Settings of the guild, the default ones if they were never changed
+    fn settings(&self, guild: GuildId) -> Result<GuildSettings, Error>;
+    /// Change the settings of the guild and save them, no other change of the storage
+    /// happening in between
+    fn update_settings(&self, guild: GuildId, edit: SettingsEdit<'_>) -> Result<(), Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{ChannelId, MessageId, Timestamp, UserId};
+
+    use super::*;
+    use crate::events::GameEvent;
+    use crate::stats::PlayerRecord;
+
+    const GUILD: GuildId = GuildId::new(1);
+
+    fn game(id: u64) -> GuildConfig {
+        GuildConfig::new(id, ChannelId::new(1), Vec::new(), GuildSettings::default())
+    }
+
+    fn saves_and_loads_games(storage: &dyn Storage) {
+        assert!(storage.games().unwrap().is_empty());
+        let mut game = game(7);
+        game.log(GameEvent::GameStarted, None);
+        storage.save_game(GUILD, &game).unwrap();
+        // Saving again only appends the new events
+        game.log(GameEvent::GameEnded, None);
+        storage.save_game(GUILD, &game).unwrap();
+        storage.save_game(GUILD, &game).unwrap();
+
+        let games = storage.games().unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].0, GUILD);
+        assert_eq!(games[0].1.id, 7);
+        assert_eq!(games[0].1.events.len(), 2);
+        let stored = storage.game(GUILD).unwrap().unwrap();
+        assert_eq!((stored.id, stored.events.len()), (7, 2));
+        assert!(storage.game(GuildId::new(2)).unwrap().is_none());
+        let events = storage.events(GUILD, 7).unwrap();
+        assert!(matches!(events[0].event, GameEvent::GameStarted));
+        assert!(matches!(events[1].event, GameEvent::GameEnded));
+
+        // The log of a deleted game is kept
+        storage.delete_game(GUILD).unwrap();
+        assert!(storage.games().unwrap().is_empty());
+        assert!(storage.game(GUILD).unwrap().is_none());
+        assert_eq!(storage.events(GUILD, 7).unwrap().len(), 2);
+        assert!(storage.events(GUILD, 8).unwrap().is_empty());
+    }
+
+    /// One of every event a game can log
+    fn every_event() -> Vec<GameEvent> {
+        let (user, channel) = (UserId::new(2), ChannelId::new(3));
+        let team = || String::from("team");
+        vec![
+            GameEvent::GameCreated {
+                admin: user,
+                admin_channel: channel,
+                questions: 2,
+            },
+            GameEvent::TeamAdded {
+                team: team(),
+                channel,
+            },
+            GameEvent::TeamRemoved { team: team() },
+            GameEvent::TeamJoined { team: team(), user },
+            GameEvent::TeamDisqualified {
+                team: team(),
+                reason: String::from("cheating"),
+            },
+            GameEvent::TeamRenamed {
+                team: team(),
+                name: String::from("other"),
+            },
+            GameEvent::TeamMoved {
+                team: team(),
+                channel,
+            },
+            GameEvent::GameStarted,
+            GameEvent::QuestionOpened {
+                number: 1,
+                question: String::from("q"),
+            },
+            GameEvent::Buzz { team: team(), user },
+            GameEvent::Guess {
+                team: team(),
+                user,
+                content: String::from("guess"),
+            },
+            GameEvent::Match {
+                number: 1,
+                team: team(),
+                user,
+                answer: String::from("a"),
+                points: 1.0,
+            },
+            GameEvent::Skip {
+                number: 1,
+                question: String::from("q"),
+                admin: user,
+            },
+            GameEvent::TimedOut {
+                number: 2,
+                question: String::from("q"),
+            },
+            GameEvent::Adjustment {
+                admin: user,
+                team: team(),
+                user: Some(user),
+                amount: -0.5,
+                reason: String::from("late"),
+            },
+            GameEvent::Paused { admin: user },
+            GameEvent::Resumed { admin: user },
+            GameEvent::GameEnded,
+        ]
+    }
+
+    fn logs_every_event(storage: &dyn Storage) {
+        let guild = GuildId::new(9);
+        let mut game = game(9);
+        for event in every_event() {
+            game.log(event, Some(MessageId::new(4)));
+        }
+        storage.save_game(guild, &game).unwrap();
+        let json = |events: &[LoggedEvent]| serde_json::to_value(events).unwrap();
+
+        let games = storage.games().unwrap();
+        let (_, loaded) = games.iter().find(|(g, _)| *g == guild).unwrap();
+        assert_eq!(json(&loaded.events), json(&game.events));
+        assert_eq!(json(&storage.events(guild, 9).unwrap()), json(&game.events));
+        storage.delete_game(guild).unwrap();
+    }
+
+    fn records_games(storage: &dyn Storage) {
+        let record = GameRecord {
+            game_id: 7,
+            ended_at: Timestamp::now(),
+            players: vec![PlayerRecord {
+                user: UserId::new(2),
+                team: String::from("red"),
+                points: 1.5,
+                answers: 2,
+                won: true,
+            }],
+        };
+        storage.record_game(GUILD, &record).unwrap();
+        let season = Season {
+            name: String::from("summer"),
+            start: Timestamp::from_unix_timestamp(0).unwrap(),
+            end: Timestamp::now(),
+        };
+        assert!(storage.add_season(GUILD, &season).unwrap());
+        assert!(!storage.add_season(GUILD, &season).unwrap());
+
+        let stats = storage.guild_stats(GUILD).unwrap();
+        assert_eq!(stats.games.len(), 1);
+        let player = &stats.games[0].players[0];
+        assert_eq!(
+            (player.user, player.points, player.answers),
+            (UserId::new(2), 1.5, 2)
+        );
+        assert!(player.won);
+        assert_eq!(stats.seasons[0].name, "summer");
+        assert!(storage
+            .guild_stats(GuildId::new(2))
+            .unwrap()
+            .games
+            .is_empty());
+
+        assert!(storage.remove_season(GUILD, "summer").unwrap());
+        assert!(!storage.remove_season(GUILD, "summer").unwrap());
+    }
+
+    fn edits_quiz_packs(storage: &dyn Storage) {
+        let question = |name: &str| Question {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        assert!(!storage
+            .update_quiz_pack(GUILD, "pack", Box::new(|_| true))
+            .unwrap());
+        storage
+            .save_quiz_pack(GUILD, "pack", &[question("first")])
+            .unwrap();
+        let added = storage.update_quiz_pack(
+            GUILD,
+            "pack",
+            Box::new(|questions| {
+                questions.push(question("second"));
+                true
+            }),
+        );
+        assert!(added.unwrap());
+        // A refused edit isn't saved
+        let refused = storage.update_quiz_pack(
+            GUILD,
+            "pack",
+            Box::new(|questions| {
+                questions.clear();
+                false
+            }),
+        );
+        assert!(refused.unwrap());
+        let pack = storage.quiz_pack(GUILD, "pack").unwrap().unwrap();
+        let names: Vec<_> = pack.iter().map(|q| q.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+    }
+
+    fn updates_settings(storage: &dyn Storage) {
+        assert_eq!(storage.settings(GUILD).unwrap().tolerance, 0);
+        // The settings read before are replaced by the saved ones
+        storage
+            .update_settings(GUILD, Box::new(|s| s.tolerance = 2))
+            .unwrap();
+        assert_eq!(storage.settings(GUILD).unwrap().tolerance, 2);
+        // Each change is made to the settings saved by the one before
+        storage
+            .update_settings(GUILD, Box::new(|s| s.buzzer_window = Some(5)))
+            .unwrap();
+        let settings = storage.settings(GUILD).unwrap();
+        assert_eq!((settings.tolerance, settings.buzzer_window), (2, Some(5)));
+        assert_eq!(storage.settings(GuildId::new(2)).unwrap().tolerance, 0);
+    }
+
+    fn round_trip(storage: &dyn Storage) {
+        saves_and_loads_games(storage);
+        logs_every_event(storage);
+        records_games(storage);
+        edits_quiz_packs(storage);
+        updates_settings(storage);
+    }
+
+    #[test]
+    fn memory() {
+        round_trip(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn sqlite() {
+        round_trip(&SqliteStorage::new(rusqlite::Connection::open_in_memory().unwrap()).unwrap());
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serenity::all::{GuildId, Timestamp, UserId};
+
+use super::{QuizEdit, SettingsEdit, Storage};
+use crate::config::{GuildConfig, Question};
+use crate::events::LoggedEvent;
+use crate::settings::GuildSettings;
+use crate::stats::{GameRecord, GuildStats, PlayerRecord, Season};
+use crate::Error;
+
+/// Schema changes, applied in order. The index of the last applied migration is kept in
+/// the `user_version` of the database, so a migration must never be edited once released
+const MIGRATIONS: &[&str] = &[
+    r"
+CREATE TABLE games (
+    guild_id INTEGER PRIMARY KEY,
+    game TEXT NOT NULL
+);
+CREATE TABLE events (
+    game_id INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    at TEXT NOT NULL,
+    message_id INTEGER,
+    event TEXT NOT NULL,
+    PRIMARY KEY (game_id, seq)
+);
+CREATE TABLE quiz_packs (
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    questions TEXT NOT NULL,
+    PRIMARY KEY (guild_id, name)
+);
+CREATE TABLE game_records (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    game_id INTEGER NOT NULL,
+    ended_at TEXT NOT NULL
+);
+CREATE TABLE player_records (
+    record_id INTEGER NOT NULL REFERENCES game_records(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL,
+    team TEXT NOT NULL,
+    points REAL NOT NULL,
+    answers INTEGER NOT NULL,
+    won INTEGER NOT NULL
+);
+CREATE INDEX player_records_record ON player_records(record_id);
+CREATE TABLE seasons (
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    PRIMARY KEY (guild_id, name)
+);
+",
+    r"
+CREATE TABLE settings (
+    guild_id INTEGER PRIMARY KEY,
+    settings TEXT NOT NULL
+);
+",
+];
+
+fn id(guild: GuildId) -> i64 {
+    guild.get() as i64
+}
+
+fn timestamp(s: String) -> rusqlite::Result<Timestamp> {
+    Timestamp::parse(&s).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Storage in a sqlite database file
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+    /// Settings of the guilds already read, they are needed by every reply of the bot
+    settings: Mutex<HashMap<GuildId, GuildSettings>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Self::new(Connection::open(path)?)
+    }
+
+    pub fn new(mut conn: Connection) -> Result<Self, Error> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            settings: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cached_settings(&self) -> MutexGuard<'_, HashMap<GuildId, GuildSettings>> {
+        self.settings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn events(conn: &Connection, guild: GuildId, game_id: u64) -> Result<Vec<LoggedEvent>, Error> {
+    let mut stmt =
+        conn.prepare("SELECT event FROM events WHERE guild_id = ?1 AND game_id = ?2 ORDER BY seq")?;
+    let rows = stmt.query_map(params![id(guild), game_id as i64], |r| {
+        r.get::<_, String>(0)
+    })?;
+    let mut events = Vec::new();
+    for row in rows {
+        // Going through a value keeps the last of the keys an old adjustment has twice
+        let event: serde_json::Value = serde_json::from_str(&row?)?;
+        events.push(serde_json::from_value(event)?);
+    }
+    Ok(events)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), Error> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn games(&self) -> Result<Vec<(GuildId, GuildConfig)>, Error> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT guild_id, game FROM games")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+        let mut games = Vec::new();
+        for row in rows {
+            let (guild, game) = row?;
+            let guild = GuildId::new(guild as u64);
+            // A game that can't be read is left in the database rather than stopping the bot
+            let game = serde_json::from_str::<GuildConfig>(&game)
+                .map_err(Error::from)
+                .and_then(|mut game| {
+                    game.events = events(&conn, guild, game.id)?;
+                    Ok(game)
+                });
+            match game {
+                Ok(game) => games.push((guild, game)),
+                Err(e) => println!("Error while loading the game of guild {guild}: {e:?}"),
+            }
+        }
+        Ok(games)
+    }
+
+    fn game(&self, guild: GuildId) -> Result<Option<GuildConfig>, Error> {
+        let conn = self.conn();
+        let game: Option<String> = conn
+            .query_row(
+                "SELECT game FROM games WHERE guild_id = ?1",
+                params![id(guild)],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let Some(game) = game else {
+            return Ok(None);
+        };
+        let mut game: GuildConfig = serde_json::from_str(&game)?;
+        game.events = events(&conn, guild, game.id)?;
+        Ok(Some(game))
+    }
+
+    fn save_game(&self, guild: GuildId, game: &GuildConfig) -> Result<(), Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO games (guild_id, game) VALUES (?1, ?2)
+             ON CONFLICT (guild_id) DO UPDATE SET game = excluded.game",
+            params![id(guild), serde_json::to_string(game)?],
+        )?;
+        let saved: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM events WHERE game_id = ?1",
+            params![game.id as i64],
+            |r| r.get(0),
+        )?;
+        for (seq, event) in game.events.iter().enumerate().skip(saved as usize) {
+            tx.execute(
+                "INSERT INTO events (game_id, seq, guild_id, at, message_id, event)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    game.id as i64,
+                    seq as i64,
+                    id(guild),
+                    event.at.to_string(),
+                    event.message.map(|m| m.get() as i64),
+                    serde_json::to_string(event)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_game(&self, guild: GuildId) -> Result<(), Error> {
+        self.conn()
+            .execute("DELETE FROM games WHERE guild_id = ?1", params![id(guild)])?;
+        Ok(())
+    }
+
+    fn events(&self, guild: GuildId, game_id: u64) -> Result<Vec<LoggedEvent>, Error> {
+        events(&self.conn(), guild, game_id)
+    }
+
+    fn quiz_packs(&self, guild: GuildId) -> Result<Vec<String>, Error> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT name FROM quiz_packs WHERE guild_id = ?1 ORDER BY name")?;
+        let names = stmt
+            .query_map(params![id(guild)], |r| r.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(names)
+    }
+
+    fn quiz_pack(&self, guild: GuildId, name: &str) -> Result<Option<Vec<Question>>, Error> {
+        let questions: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT questions FROM quiz_packs WHERE guild_id = ?1 AND name = ?2",
+                params![id(guild), name],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(questions.map(|q| serde_json::from_str(&q)).transpose()?)
+    }
+
+    fn save_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        questions: &[Question],
+    ) -> Result<(), Error> {
+        self.conn().execute(
+            "INSERT INTO quiz_packs (guild_id, name, questions) VALUES (?1, ?2, ?3)
+             ON CONFLICT (guild_id, name) DO UPDATE SET questions = excluded.questions",
+            params![id(guild), name, serde_json::to_string(questions)?],
+        )?;
+        Ok(())
+    }
+
+    fn update_quiz_pack(
+        &self,
+        guild: GuildId,
+        name: &str,
+        edit: QuizEdit<'_>,
+    ) -> Result<bool, Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let questions: Option<String> = tx
+            .query_row(
+                "SELECT questions FROM quiz_packs WHERE guild_id = ?1 AND name = ?2",
+                params![id(guild), name],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let Some(questions) = questions else {
+            return Ok(false);
+        };
+        let mut questions: Vec<Question> = serde_json::from_str(&questions)?;
+        if edit(&mut questions) {
+            tx.execute(
+                "UPDATE quiz_packs SET questions = ?3 WHERE guild_id = ?1 AND name = ?2",
+                params![id(guild), name, serde_json::to_string(&questions)?],
+            )?;
+            tx.commit()?;
+        }
+        Ok(true)
+    }
+
+    fn delete_quiz_pack(&self, guild: GuildId, name: &str) -> Result<bool, Error> {
+        let deleted = self.conn().execute(
+            "DELETE FROM quiz_packs WHERE guild_id = ?1 AND name = ?2",
+            params![id(guild), name],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn guild_stats(&self, guild: GuildId) -> Result<GuildStats, Error> {
+        let conn = self.conn();
+        let mut stats = GuildStats::default();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, game_id, ended_at FROM game_records WHERE guild_id = ?1 ORDER BY id",
+        )?;
+        let records = stmt
+            .query_map(params![id(guild)], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, i64>(1)?,
+                    timestamp(r.get(2)?)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stmt = conn.prepare(
+            "SELECT user_id, team, points, answers, won FROM player_records WHERE record_id = ?1",
+        )?;
+        for (record, game_id, ended_at) in records {
+            let players = stmt
+                .query_map(params![record], |r| {
+                    Ok(PlayerRecord {
+                        user: UserId::new(r.get::<_, i64>(0)? as u64),
+                        team: r.get(1)?,
+                        points: r.get(2)?,
+                        answers: r.get::<_, i64>(3)? as usize,
+                        won: r.get(4)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            stats.games.push(GameRecord {
+                game_id: game_id as u64,
+                ended_at,
+                players,
+            });
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT name, start, end FROM seasons WHERE guild_id = ?1 ORDER BY start")?;
+        stats.seasons = stmt
+            .query_map(params![id(guild)], |r| {
+                Ok(Season {
+                    name: r.get(0)?,
+                    start: timestamp(r.get(1)?)?,
+                    end: timestamp(r.get(2)?)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(stats)
+    }
+
+    fn record_game(&self, guild: GuildId, record: &GameRecord) -> Result<(), Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO game_records (guild_id, game_id, ended_at) VALUES (?1, ?2, ?3)",
+            params![
+                id(guild),
+                record.game_id as i64,
+                record.ended_at.to_string()
+            ],
+        )?;
+        let record_id = tx.last_insert_rowid();
+        for player in &record.players {
+            tx.execute(
+                "INSERT INTO player_records (record_id, user_id, team, points, answers, won)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    record_id,
+                    player.user.get() as i64,
+                    player.team,
+                    player.points,
+                    player.answers as i64,
+                    player.won
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn add_season(&self, guild: GuildId, season: &Season) -> Result<bool, Error> {
+        let added = self.conn().execute(
+            "INSERT OR IGNORE INTO seasons (guild_id, name, start, end) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                id(guild),
+                season.name,
+                season.start.to_string(),
+                season.end.to_string()
+            ],
+        )?;
+        Ok(added > 0)
+    }
+
+    fn remove_season(&self, guild: GuildId, name: &str) -> Result<bool, Error> {
+        let removed = self.conn().execute(
+            "DELETE FROM seasons WHERE guild_id = ?1 AND name = ?2",
+            params![id(guild), name],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn settings(&self, guild: GuildId) -> Result<GuildSettings, Error> {
+        if let Some(settings) = self.cached_settings().get(&guild) {
+            return Ok(settings.clone());
+        }
+        let settings: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT settings FROM settings WHERE guild_id = ?1",
+                params![id(guild)],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let settings: GuildSettings = settings
+            .map(|s| serde_json::from_str(&s))
+            .transpose()?
+            .unwrap_or_default();
+        self.cached_settings().insert(guild, settings.clone());
+        Ok(settings)
+    }
+
+    fn update_settings(&self, guild: GuildId, edit: SettingsEdit<'_>) -> Result<(), Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let settings: Option<String> = tx
+            .query_row(
+                "SELECT settings FROM settings WHERE guild_id = ?1",
+                params![id(guild)],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let mut settings: GuildSettings = settings
+            .map(|s| serde_json::from_str(&s))
+            .transpose()?
+            .unwrap_or_default();
+        edit(&mut settings);
+        tx.execute(
+            "INSERT INTO settings (guild_id, settings) VALUES (?1, ?2)
+             ON CONFLICT (guild_id) DO UPDATE SET settings = excluded.settings",
+            params![id(guild), serde_json::to_string(&settings)?],
+        )?;
+        tx.commit()?;
+        // Still holding the connection, so the cache gets the changes in the order they were saved
+        self.cached_settings().insert(guild, settings);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::{ChannelId, UserId};
+
+    use super::*;
+    use crate::config::Adjustment;
+    use crate::events::GameEvent;
+
+    const GUILD: GuildId = GuildId::new(1);
+
+    #[test]
+    fn reads_adjustments_logged_with_two_times() {
+        let storage = SqliteStorage::new(Connection::open_in_memory().unwrap()).unwrap();
+        let mut game = GuildConfig::new(3, ChannelId::new(1), Vec::new(), Default::default());
+        game.record_adjustment(
+            Adjustment {
+                admin: UserId::new(1),
+                team: String::from("team"),
+                user: None,
+                amount: 2.0,
+                reason: String::from("bonus"),
+                at: Timestamp::now(),
+            },
+            None,
+        );
+        storage.save_game(GUILD, &game).unwrap();
+        storage
+            .conn()
+            .execute(
+                "INSERT INTO events (game_id, seq, guild_id, at, message_id, event)
+                 VALUES (3, 1, 1, '2024-01-01T00:00:00Z', NULL, ?1)",
+                params![r#"{"at":"2024-01-01T00:00:00Z","message":null,"event":"adjustment","admin":"1","team":"team","user":"2","amount":-1.0,"reason":"late","at":"2024-01-01T00:00:01Z"}"#],
+            )
+            .unwrap();
+
+        let events = storage.game(GUILD).unwrap().unwrap().events;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[0].event,
+            GameEvent::Adjustment { amount: 2.0, .. }
+        ));
+        let GameEvent::Adjustment { user, amount, .. } = &events[1].event else {
+            panic!("{:?}", events[1].event);
+        };
+        assert_eq!((*user, *amount), (Some(UserId::new(2)), -1.0));
+    }
+
+    #[test]
+    fn skips_the_games_that_cant_be_read() {
+        let storage = SqliteStorage::new(Connection::open_in_memory().unwrap()).unwrap();
+        let game = GuildConfig::new(3, ChannelId::new(1), Vec::new(), Default::default());
+        storage.save_game(GUILD, &game).unwrap();
+        storage
+            .conn()
+            .execute(
+                "INSERT INTO games (guild_id, game) VALUES (2, '{\"id\": 4')",
+                [],
+            )
+            .unwrap();
+        let games = storage.games().unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!((games[0].0, games[0].1.id), (GUILD, 3));
+        assert!(storage.game(GuildId::new(2)).is_err());
+    }
+}
@@ -0,0 +1,803 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use serenity::all::{
+    ButtonStyle, ChannelId, ChannelType, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateChannel, EditRole, GuildId, Mentionable, PermissionOverwrite, PermissionOverwriteType,
+    Permissions, RoleId, UserId,
+};
+
+use crate::blindtest::{autocomplete_team, is_text_channel};
+use crate::buzzer;
+use crate::config;
+use crate::events::GameEvent;
+use crate::i18n;
+use crate::settings::Tone;
+use crate::utils::{self, create_embed, respond};
+use crate::{tr, Context, Data, Error};
+
+/// Custom id of the button of the signup message
+pub const SIGNUP_BUTTON: &str = "blindtest-signup";
+
+/// Most teams that can be created at once
+const MAX_TEAMS: usize = 25;
+
+fn team_permissions() -> Permissions {
+    Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::READ_MESSAGE_HISTORY
+}
+
+/// Teams created by [`provision_teams`], and why the others couldn't be
+#[derive(Default)]
+struct Provisioning {
+    /// A line for each team created, with its role and channel
+    created: Vec<String>,
+    /// A line for each Discord error, naming what couldn't be created
+    errors: Vec<String>,
+}
+
+/// Create a role and a private channel for each of `names`, and add them as teams of the
+/// game. Everything is recorded in the game as soon as it is created, a team that couldn't
+/// be created doesn't stop the next ones
+async fn provision_teams(
+    ctx: Context<'_>,
+    category: Option<ChannelId>,
+    names: Vec<String>,
+) -> Result<Provisioning, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let lang = i18n::lang(ctx);
+    let mut provisioning = Provisioning::default();
+    // Nobody but the bot sees the category, each team channel is then opened to its role
+    let hidden = PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::VIEW_CHANNEL,
+        kind: PermissionOverwriteType::Role(guild_id.everyone_role()),
+    };
+    let bot = PermissionOverwrite {
+        allow: team_permissions(),
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Member(ctx.framework().bot_id),
+    };
+    let category = match category {
+        Some(category) => category,
+        None => {
+            let created = guild_id
+                .create_channel(
+                    ctx,
+                    CreateChannel::new("Blindtest")
+                        .kind(ChannelType::Category)
+                        .permissions(vec![hidden.clone(), bot.clone()]),
+                )
+                .await;
+            let category = match created {
+                Ok(category) => category.id,
+                Err(e) => {
+                    let error = tr!(lang, "The category: {error}", error = e);
+                    provisioning.errors.push(error);
+                    return Ok(provisioning);
+                }
+            };
+            ctx.data()
+                .games
+                .edit(guild_id, move |game, _| {
+                    if let Some(game) = game {
+                        game.provisioned.category = Some(category);
+                    }
+                })
+                .await?;
+            category
+        }
+    };
+
+    for name in names {
+        let overwrites = [hidden.clone(), bot.clone()];
+        match provision_team(ctx, category, overwrites, name.clone()).await {
+            Ok(line) => provisioning.created.extend(line),
+            Err(e) => {
+                let error = tr!(lang, "`{team}`: {error}", team = name, error = e);
+                provisioning.errors.push(error);
+            }
+        }
+    }
+    Ok(provisioning)
+}
+
+/// Create the role and the channel of a team in `category`, the channel being hidden by
+/// `overwrites` from everyone but the role
+async fn provision_team(
+    ctx: Context<'_>,
+    category: ChannelId,
+    overwrites: [PermissionOverwrite; 2],
+    name: String,
+) -> Result<Option<String>, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let games = &ctx.data().games;
+    let role = guild_id
+        .create_role(ctx, EditRole::new().name(&name).mentionable(true))
+        .await?
+        .id;
+    games
+        .edit(guild_id, move |game, _| {
+            if let Some(game) = game {
+                game.provisioned.roles.push(role);
+            }
+        })
+        .await?;
+    let member = PermissionOverwrite {
+        allow: team_permissions(),
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Role(role),
+    };
+    let channel = guild_id
+        .create_channel(
+            ctx,
+            CreateChannel::new(&name)
+                .kind(ChannelType::Text)
+                .category(category)
+                .permissions(overwrites.into_iter().chain([member])),
+        )
+        .await?
+        .id;
+    games
+        .edit(guild_id, move |game, _| {
+            let game = game.as_mut()?;
+            game.provisioned.channels.push(channel);
+            game.teams.push(config::Team {
+                name: name.clone(),
+                channel,
+                leaderboard: HashMap::new(),
+                total_points: 0.0,
+                role: Some(role),
+                disqualified: None,
+            });
+            game.log(
+                GameEvent::TeamAdded {
+                    team: name,
+                    channel,
+                },
+                None,
+            );
+            Some(tr!(
+                game.settings.language,
+                "-> {role} in {channel}",
+                role = role.mention(),
+                channel = channel.mention()
+            ))
+        })
+        .await
+}
+
+/// Create a category with a private channel and a role for each team
+#[poise::command(slash_command, rename = "create", guild_only)]
+pub async fn create_teams(
+    ctx: Context<'_>,
+    #[description = "number of teams, named Team 1, Team 2..."] count: Option<usize>,
+    #[description = "comma separated team names"] names: Option<String>,
+) -> Result<(), Error> {
+    let names: Vec<String> = match (count, names) {
+        (_, Some(names)) => names
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(String::from)
+            .collect(),
+        (Some(count), None) => (1..=count)
+            .map(|i| tr!(i18n::lang(ctx), "Team {number}", number = i))
+            .collect(),
+        (None, None) => {
+            utils::send_error(ctx, "Give either a number of teams or their names").await?;
+            return Ok(());
+        }
+    };
+    if names.is_empty() || names.len() > MAX_TEAMS {
+        let msg = tr!(
+            i18n::lang(ctx),
+            "You can create between 1 and {max} teams",
+            max = MAX_TEAMS
+        );
+        utils::send_error(ctx, msg).await?;
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(game) = ctx.data().games.get(guild_id).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+    if let Some(name) = names
+        .iter()
+        .enumerate()
+        .find(|(i, n)| names[..*i].contains(n) || game.teams.iter().any(|t| &t.name == *n))
+        .map(|(_, n)| n)
+    {
+        let msg = tr!(
+            game.settings.language,
+            "A team already exists with the name `{team}` !",
+            team = name
+        );
+        utils::send_error(ctx, msg).await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let provisioning = provision_teams(ctx, game.provisioned.category, names).await?;
+    let lang = game.settings.language;
+    let failed = tr!(
+        lang,
+        "These couldn't be created:\n{errors}",
+        errors = provisioning.errors.join("\n")
+    );
+    if provisioning.created.is_empty() && !provisioning.errors.is_empty() {
+        return utils::send_error(ctx, failed).await;
+    }
+    let mut msg = tr!(
+        lang,
+        "Created the teams:\n{teams}",
+        teams = provisioning.created.join("\n")
+    );
+    if !provisioning.errors.is_empty() {
+        msg = format!("{msg}\n\n{failed}");
+    }
+    utils::send_reply(ctx, msg).await
+}
+
+/// Delete the channels and roles created for the teams
+#[poise::command(slash_command, rename = "cleanup", guild_only)]
+pub async fn cleanup_teams(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let taken = ctx
+        .data()
+        .games
+        .edit(guild_id, |game, _| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            if matches!(game.engine.state, config::GameState::Started) {
+                return Err("The game is still running");
+            }
+            let provisioned = std::mem::take(&mut game.provisioned);
+            if !matches!(game.engine.state, config::GameState::Ended) {
+                let removed: Vec<_> = game
+                    .teams
+                    .iter()
+                    .filter(|t| provisioned.channels.contains(&t.channel))
+                    .map(|t| t.name.clone())
+                    .collect();
+                game.teams
+                    .retain(|t| !provisioned.channels.contains(&t.channel));
+                for team in removed {
+                    game.log(GameEvent::TeamRemoved { team }, None);
+                }
+            }
+            Ok((provisioned, game.settings.language))
+        })
+        .await?;
+    let (provisioned, lang) = match taken {
+        Ok(taken) => taken,
+        Err(e) => return utils::send_error(ctx, e).await,
+    };
+    ctx.defer().await?;
+
+    // Whatever was already deleted by hand is simply forgotten, the rest is kept in the game
+    // to be deleted by the next cleanup
+    let mut kept = config::Provisioned::default();
+    let mut errors = Vec::new();
+    for channel in &provisioned.channels {
+        if let Err(e) = channel.delete(ctx).await.map(drop).or_else(forget_missing) {
+            errors.push(format!("{}: {e}", channel.mention()));
+            kept.channels.push(*channel);
+        }
+    }
+    if let Some(category) = provisioned.category {
+        if let Err(e) = category.delete(ctx).await.map(drop).or_else(forget_missing) {
+            errors.push(format!("{}: {e}", category.mention()));
+            kept.category = Some(category);
+        }
+    }
+    for role in &provisioned.roles {
+        if let Err(e) = guild_id
+            .delete_role(ctx, *role)
+            .await
+            .or_else(forget_missing)
+        {
+            errors.push(format!("{}: {e}", role.mention()));
+            kept.roles.push(*role);
+        }
+    }
+    let channels = provisioned.channels.len() + usize::from(provisioned.category.is_some())
+        - kept.channels.len()
+        - usize::from(kept.category.is_some());
+    let roles = provisioned.roles.len() - kept.roles.len();
+    let msg = tr!(
+        lang,
+        "Deleted {channels} channels and {roles} roles",
+        channels = channels,
+        roles = roles
+    );
+    if errors.is_empty() {
+        return utils::send_reply(ctx, msg).await;
+    }
+    ctx.data()
+        .games
+        .edit(guild_id, move |game, _| {
+            if let Some(game) = game {
+                game.provisioned.channels.extend(kept.channels);
+                game.provisioned.category = game.provisioned.category.or(kept.category);
+                game.provisioned.roles.extend(kept.roles);
+            }
+        })
+        .await?;
+    let msg = tr!(
+        lang,
+        "{deleted}\nThese couldn't be deleted, run the cleanup again to retry:\n{errors}",
+        deleted = msg,
+        errors = errors.join("\n")
+    );
+    utils::send_error(ctx, msg).await
+}
+
+/// Treat a Discord resource that no longer exists as deleted
+fn forget_missing(error: serenity::Error) -> Result<(), Error> {
+    match &error {
+        serenity::Error::Http(serenity::all::HttpError::UnsuccessfulRequest(response))
+            if response.status_code == serenity::all::StatusCode::NOT_FOUND =>
+        {
+            Ok(())
+        }
+        _ => Err(error.into()),
+    }
+}
+
+/// How the players are spread between the teams
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum Balance {
+    #[default]
+    Random,
+    /// Spread the best players of the previous games
+    Skill,
+}
+
+/// Post a message with a button the players press to join the game
+#[poise::command(slash_command, rename = "signup", guild_only)]
+pub async fn signup_teams(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(game) = ctx.data().games.get(ctx.guild_id().unwrap()).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+    if !matches!(game.engine.state, config::GameState::Configuring) {
+        utils::send_error(ctx, "The game has already started").await?;
+        return Ok(());
+    }
+    let settings = game.settings;
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(create_embed(
+                &settings,
+                Tone::Success,
+                "Join the blindtest",
+                "Press the button to join the game, press it again to leave",
+            ))
+            .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+                SIGNUP_BUTTON,
+            )
+            .label(i18n::translate(settings.language, "Join"))
+            .emoji('🎵')
+            .style(ButtonStyle::Success)])]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Handle a press on the signup button
+pub async fn handle_interaction(
+    ctx: &serenity::all::Context,
+    interaction: &ComponentInteraction,
+    data: &Data,
+) -> Result<(), Error> {
+    if interaction.data.custom_id != SIGNUP_BUTTON {
+        return Ok(());
+    }
+    let Some(guild_id) = interaction.guild_id else {
+        return Ok(());
+    };
+    let user = interaction.user.id;
+    let locale = interaction.locale.clone();
+    let signed = data
+        .games
+        .edit(guild_id, move |game, _| {
+            let game = game
+                .as_mut()
+                .filter(|g| matches!(g.engine.state, config::GameState::Configuring))?;
+            let msg = match game.signups.iter().position(|u| *u == user) {
+                Some(pos) => {
+                    game.signups.remove(pos);
+                    "You left the blindtest"
+                }
+                None => {
+                    game.signups.push(user);
+                    "You joined the blindtest"
+                }
+            };
+            let settings = game.settings.for_locale(&locale);
+            let msg = tr!(
+                settings.language,
+                "{msg}, {count} players signed up",
+                msg = i18n::translate(settings.language, msg),
+                count = game.signups.len()
+            );
+            Some(create_embed(&settings, Tone::Success, "Success!", msg))
+        })
+        .await?;
+    let msg = match signed {
+        Some(msg) => msg,
+        None => create_embed(
+            &data
+                .storage
+                .settings(guild_id)?
+                .for_locale(&interaction.locale),
+            Tone::Error,
+            "Error!",
+            "The signups are closed",
+        ),
+    };
+    respond(ctx, interaction, msg, true).await
+}
+
+/// Split `players` into `teams` teams of the same size
+fn draft(
+    ctx: Context<'_>,
+    mut players: Vec<UserId>,
+    teams: usize,
+    balance: Balance,
+) -> Result<Vec<Vec<UserId>>, Error> {
+    let mut out = vec![Vec::new(); teams];
+    match balance {
+        Balance::Random => {
+            players.shuffle(&mut rand::thread_rng());
+            for (i, player) in players.into_iter().enumerate() {
+                out[i % teams].push(player);
+            }
+        }
+        Balance::Skill => {
+            // Snake draft on the average points per game, newcomers last
+            let stats = ctx.data().storage.guild_stats(ctx.guild_id().unwrap())?;
+            let skill = |user: &UserId| {
+                let s = stats.player(*user, None);
+                if s.games == 0 {
+                    0.0
+                } else {
+                    s.points / s.games as f64
+                }
+            };
+            players.sort_by(|lhs, rhs| skill(rhs).total_cmp(&skill(lhs)));
+            for (i, player) in players.into_iter().enumerate() {
+                let pos = i % teams;
+                let pos = if (i / teams) % 2 == 0 {
+                    pos
+                } else {
+                    teams - 1 - pos
+                };
+                out[pos].push(player);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Give `role` to `user`, taking the other `roles` of the teams from them
+async fn give_role(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    user: UserId,
+    role: RoleId,
+    roles: &[RoleId],
+) -> Result<(), Error> {
+    let member = guild_id.member(ctx, user).await?;
+    for other in member
+        .roles
+        .iter()
+        .filter(|r| **r != role && roles.contains(r))
+    {
+        member.remove_role(ctx, *other).await?;
+    }
+    if !member.roles.contains(&role) {
+        member.add_role(ctx, role).await?;
+    }
+    Ok(())
+}
+
+/// Spread the players that signed up between teams, creating the missing ones
+#[poise::command(slash_command, rename = "balance", guild_only)]
+pub async fn balance_teams(
+    ctx: Context<'_>,
+    #[description = "number of teams"] count: usize,
+    #[description = "how to spread the players"] balance: Option<Balance>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(game) = ctx.data().games.get(guild_id).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+    if !matches!(game.engine.state, config::GameState::Configuring) {
+        utils::send_error(ctx, "The game has already started").await?;
+        return Ok(());
+    }
+    if count == 0 || count > MAX_TEAMS {
+        let msg = tr!(
+            game.settings.language,
+            "You can have between 1 and {max} teams",
+            max = MAX_TEAMS
+        );
+        utils::send_error(ctx, msg).await?;
+        return Ok(());
+    }
+    if game.signups.len() < count {
+        let msg = tr!(
+            game.settings.language,
+            "Only {count} players signed up",
+            count = game.signups.len()
+        );
+        utils::send_error(ctx, msg).await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+
+    let missing = count.saturating_sub(game.teams.iter().filter(|t| t.role.is_some()).count());
+    let names = (1..)
+        .map(|i| tr!(game.settings.language, "Team {number}", number = i))
+        .filter(|n| game.teams.iter().all(|t| t.name != *n))
+        .take(missing)
+        .collect();
+    let provisioning = provision_teams(ctx, game.provisioned.category, names).await?;
+    if !provisioning.errors.is_empty() {
+        let msg = tr!(
+            game.settings.language,
+            "The teams couldn't be created, no player was moved:\n{errors}",
+            errors = provisioning.errors.join("\n")
+        );
+        return utils::send_error(ctx, msg).await;
+    }
+    let Some(game) = ctx.data().games.get(guild_id).await? else {
+        utils::send_error(ctx, "No game exists").await?;
+        return Ok(());
+    };
+
+    let drafted = draft(
+        ctx,
+        game.signups.clone(),
+        count,
+        balance.unwrap_or_default(),
+    )?;
+    // Only the teams being filled are touched
+    let teams: Vec<_> = game
+        .teams
+        .iter()
+        .filter_map(|t| Some((t.name.clone(), t.role?)))
+        .take(count)
+        .collect();
+    let roles: Vec<_> = teams.iter().map(|(_, role)| *role).collect();
+    let mut msg = String::new();
+    let mut joined = Vec::new();
+    let mut failed = Vec::new();
+    for ((name, role), players) in teams.into_iter().zip(drafted) {
+        let mut placed = Vec::new();
+        for user in players {
+            if let Err(e) = give_role(ctx, guild_id, user, role, &roles).await {
+                println!("Error while moving {user} to team {name}: {e:?}");
+                failed.push(user.mention().to_string());
+                continue;
+            }
+            joined.push(GameEvent::TeamJoined {
+                team: name.clone(),
+                user,
+            });
+            placed.push(user.mention().to_string());
+        }
+        msg.push_str(&format!("**{name}**: {}\n", placed.join(", ")));
+    }
+    ctx.data()
+        .games
+        .edit(guild_id, move |game, outbox| {
+            if let Some(game) = game {
+                for event in joined {
+                    game.log(event, None);
+                }
+                outbox.admin(create_embed(&game.settings, Tone::Success, "Teams", msg));
+            }
+        })
+        .await?;
+    let mut msg = tr!(
+        game.settings.language,
+        "Spread {players} players between {count} teams",
+        players = game.signups.len() - failed.len(),
+        count = count
+    );
+    if !failed.is_empty() {
+        msg.push('\n');
+        msg += &tr!(
+            game.settings.language,
+            "Couldn't give their team role to {players}",
+            players = failed.join(", ")
+        );
+    }
+    utils::send_reply(ctx, msg).await
+}
+
+/// Disqualify a team of the running game, its points are kept in the results
+#[poise::command(slash_command, rename = "disqualify", guild_only)]
+pub async fn disqualify_team(
+    ctx: Context<'_>,
+    #[description = "team name"]
+    #[autocomplete = "autocomplete_team"]
+    name: String,
+    #[description = "why the team is disqualified"] reason: String,
+) -> Result<(), Error> {
+    let disqualified = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            if !matches!(game.engine.state, config::GameState::Started) {
+                return Err("The game isn't running, use `/game teams remove` instead");
+            }
+            let Some(team) = game
+                .teams
+                .iter_mut()
+                .find(|t| t.name == name && t.disqualified.is_none())
+            else {
+                return Err("No team in the game has this name !");
+            };
+            team.disqualified = Some(reason.clone());
+            let channel = team.channel;
+            game.log(
+                GameEvent::TeamDisqualified {
+                    team: name.clone(),
+                    reason: reason.clone(),
+                },
+                None,
+            );
+            if game
+                .buzzer
+                .as_ref()
+                .and_then(|b| b.holder.as_ref())
+                .is_some_and(|h| h.channel == channel)
+            {
+                buzzer::release(outbox, game, "was disqualified");
+            }
+            outbox.broadcast(
+                [channel],
+                create_embed(
+                    &game.settings,
+                    Tone::Error,
+                    "Disqualified",
+                    tr!(
+                        game.settings.language,
+                        "Your team has been disqualified: {reason}",
+                        reason = reason
+                    ),
+                ),
+            );
+            Ok(tr!(
+                game.settings.language,
+                "Disqualified team `{team}`",
+                team = name
+            ))
+        })
+        .await?;
+    match disqualified {
+        Ok(msg) => utils::send_reply(ctx, msg).await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Rename a team of the current game
+#[poise::command(slash_command, rename = "rename", guild_only)]
+pub async fn rename_team(
+    ctx: Context<'_>,
+    #[description = "team name"]
+    #[autocomplete = "autocomplete_team"]
+    name: String,
+    #[description = "new team name"] new_name: String,
+) -> Result<(), Error> {
+    let renamed = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            let Some(channel) = game
+                .teams
+                .iter()
+                .find(|t| t.name == name)
+                .map(|t| t.channel)
+            else {
+                return Err("No team exists with this name !");
+            };
+            if game.teams.iter().any(|t| t.name == new_name) {
+                return Err("A team already exists with that name !");
+            }
+            game.rename_team(&name, &new_name);
+            outbox.broadcast(
+                [channel],
+                create_embed(
+                    &game.settings,
+                    Tone::Success,
+                    "Team renamed",
+                    tr!(
+                        game.settings.language,
+                        "Your team is now named `{team}`",
+                        team = new_name
+                    ),
+                ),
+            );
+            Ok(tr!(
+                game.settings.language,
+                "Renamed team `{team}` to `{name}`",
+                team = name,
+                name = new_name
+            ))
+        })
+        .await?;
+    match renamed {
+        Ok(msg) => utils::send_reply(ctx, msg).await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
+
+/// Make a team of the current game answer in another channel
+#[poise::command(slash_command, rename = "move", guild_only)]
+pub async fn move_team(
+    ctx: Context<'_>,
+    #[description = "team name"]
+    #[autocomplete = "autocomplete_team"]
+    name: String,
+    #[description = "new team discord channel"] channel: ChannelId,
+) -> Result<(), Error> {
+    if !is_text_channel(ctx, channel).await? {
+        return Ok(());
+    }
+    let moved = ctx
+        .data()
+        .games
+        .edit(ctx.guild_id().unwrap(), move |game, outbox| {
+            let Some(game) = game else {
+                return Err("No game exists");
+            };
+            let Some(old) = game
+                .teams
+                .iter()
+                .find(|t| t.name == name)
+                .map(|t| t.channel)
+            else {
+                return Err("No team exists with this name !");
+            };
+            if game.teams.iter().any(|t| t.channel == channel) {
+                return Err("A team already exists with that channel !");
+            }
+            game.move_team(&name, channel);
+            let msg = create_embed(
+                &game.settings,
+                Tone::Success,
+                "Team moved",
+                tr!(
+                    game.settings.language,
+                    "Team `{team}` now answers in {channel}",
+                    team = name,
+                    channel = channel.mention()
+                ),
+            );
+            outbox.broadcast([old, channel], msg);
+            Ok(tr!(
+                game.settings.language,
+                "Moved team `{team}` to {channel}",
+                team = name,
+                channel = channel.mention()
+            ))
+        })
+        .await?;
+    match moved {
+        Ok(msg) => utils::send_reply(ctx, msg).await,
+        Err(e) => utils::send_error(ctx, e).await,
+    }
+}
@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use serenity::all::CreateEmbed;
+
+use crate::config::{Answer, Question};
+use crate::settings::{GuildSettings, Language, Tone};
+use crate::utils::create_embeds;
+use crate::{i18n, tr};
+
+/// Messages of the bot a guild can rewrite with `/config template`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    poise::ChoiceParameter,
+)]
+pub enum Template {
+    /// Sent to the teams when the game starts
+    #[name = "intro"]
+    Intro,
+    /// Sent to every channel when a team finds an answer
+    #[name = "answer found"]
+    AnswerFound,
+    /// Sent to every channel once all the answers of a question were found
+    #[name = "question done"]
+    QuestionDone,
+    /// Sent to every channel after the last question
+    #[name = "game over"]
+    GameOver,
+    /// Sent to the admin channel with the answers of the next question
+    #[name = "next question"]
+    NextQuestion,
+}
+
+impl Template {
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Intro => "New game !",
+            Self::AnswerFound => "Answer found !",
+            Self::QuestionDone | Self::GameOver => "All answer found",
+            Self::NextQuestion => "Next question !",
+        }
+    }
+
+    fn tone(self) -> Tone {
+        match self {
+            Self::Intro | Self::NextQuestion => Tone::Info,
+            Self::AnswerFound | Self::QuestionDone | Self::GameOver => Tone::Success,
+        }
+    }
+
+    /// Text used when the guild didn't write its own, in English
+    pub fn default_text(self) -> &'static str {
+        match self {
+            Self::Intro => {
+                r"We will soon start the blindtest !
+If you know the name of the song, and the author/band that made it, send a message **in lowercase** here
+One point will be given for the author/band name and one point for the song name
+Be careful about mistakes in the response, and remember: One message for the song name, and one for the band name
+
+ENJOY :D"
+            }
+            Self::AnswerFound => {
+                "{user} found an answer !\nIt was: `{answer}`\nThey now have {points} points !"
+            }
+            Self::QuestionDone => "All anser were found for the current questions !",
+            Self::GameOver => "The game is finished\n Hope you had fun !",
+            Self::NextQuestion => {
+                "Here are the next answers for the question: `{question}`\n\n{answers}\n{details}There are {remaining} remaining questions"
+            }
+        }
+    }
+
+    /// Names of the `{placeholders}` the template can use
+    pub fn placeholders(self) -> &'static [&'static str] {
+        match self {
+            Self::Intro => &["teams", "questions"],
+            Self::AnswerFound => &["user", "team", "answer", "gained", "points"],
+            Self::QuestionDone => &["question", "remaining"],
+            Self::GameOver => &["questions"],
+            Self::NextQuestion => &["question", "answers", "details", "remaining"],
+        }
+    }
+
+    /// Placeholders of `text` this template doesn't know about
+    pub fn unknown_placeholders(self, text: &str) -> Vec<String> {
+        let mut unknown = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            rest = &rest[start + 1..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let name = &rest[..end];
+            if !name.is_empty()
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                && !self.placeholders().contains(&name)
+            {
+                unknown.push(name.to_owned());
+            }
+            rest = &rest[end + 1..];
+        }
+        unknown
+    }
+}
+
+/// Text of a template in the guild language, with its placeholders filled
+pub fn render(settings: &GuildSettings, template: Template, args: &[(&str, String)]) -> String {
+    let text = match settings.templates.get(&template) {
+        Some(text) => text.as_str(),
+        None => i18n::translate(settings.language, template.default_text()),
+    };
+    i18n::fill(text, args)
+}
+
+/// Embeds of a template, titled and coloured like the other messages of its kind
+pub fn embed(
+    settings: &GuildSettings,
+    template: Template,
+    args: &[(&str, String)],
+) -> Vec<CreateEmbed> {
+    create_embeds(
+        settings,
+        template.tone(),
+        template.title(),
+        render(settings, template, args),
+    )
+}
+
+/// Answers of a question, the names of an answer with aliases joined in a tree
+pub fn answer_tree(question: &Question) -> String {
+    let mut lines = Vec::new();
+    for answer in &question.answer {
+        match answer {
+            Answer::SingleAnswer(name) => lines.push(format!("-> `{name}`")),
+            Answer::MutlipleAnswer(aliases, _) if aliases.len() == 1 => {
+                lines.push(format!("-> `{}`", aliases[0]))
+            }
+            Answer::MutlipleAnswer(aliases, _) => {
+                for (i, alias) in aliases.iter().enumerate() {
+                    let branch = match i {
+                        0 => '┌',
+                        _ if i == aliases.len() - 1 => '└',
+                        _ => '│',
+                    };
+                    lines.push(format!("{branch} -> `{alias}`"));
+                }
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// What the host knows about a question besides its answers, one line each
+pub fn question_details(question: &Question, lang: Language) -> String {
+    let mut details = String::new();
+    if let Some(year) = question.year {
+        details += &tr!(lang, "Year: {year}", year = year);
+        details.push('\n');
+    }
+    if let Some(hint) = &question.hint {
+        details += &tr!(lang, "Hint: {hint}", hint = hint);
+        details.push('\n');
+    }
+    if let Some(seconds) = question.time_limit {
+        details += &tr!(lang, "Time limit: {seconds}s", seconds = seconds);
+        details.push('\n');
+    }
+    details
+}
+
+/// Arguments of the next question template for `question`, followed by `remaining` others
+pub fn question_args(
+    question: Option<&Question>,
+    remaining: usize,
+    lang: Language,
+) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "question",
+            question.map(|q| q.name.clone()).unwrap_or_default(),
+        ),
+        ("answers", question.map(answer_tree).unwrap_or_default()),
+        (
+            "details",
+            question
+                .map(|q| question_details(q, lang))
+                .unwrap_or_default(),
+        ),
+        ("remaining", remaining.to_string()),
+    ]
+}
+
+/// Arguments of the next question template for the current question of a game
+pub fn next_question_args(game: &crate::config::GuildConfig) -> Vec<(&'static str, String)> {
+    question_args(
+        game.engine.current(),
+        game.engine.questions.len().saturating_sub(1),
+        game.settings.language,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATES: [Template; 5] = [
+        Template::Intro,
+        Template::AnswerFound,
+        Template::QuestionDone,
+        Template::GameOver,
+        Template::NextQuestion,
+    ];
+
+    #[test]
+    fn finds_the_unknown_placeholders() {
+        let text = "{user} has {points} points, {score} {a b} {} {unclosed";
+        assert_eq!(Template::AnswerFound.unknown_placeholders(text), ["score"]);
+        assert_eq!(
+            Template::GameOver.unknown_placeholders("{user} {questions}"),
+            ["user"]
+        );
+    }
+
+    #[test]
+    fn default_texts_only_use_known_placeholders() {
+        for template in TEMPLATES {
+            for lang in [Language::English, Language::French] {
+                let text = i18n::translate(lang, template.default_text());
+                assert!(template.unknown_placeholders(text).is_empty(), "{text}");
+            }
+        }
+    }
+
+    #[test]
+    fn renders_the_text_rewritten_by_the_guild() {
+        let mut settings = GuildSettings::default();
+        let args = [("questions", String::from("3"))];
+        assert!(render(&settings, Template::GameOver, &args).starts_with("The game is finished"));
+        settings.templates.insert(
+            Template::GameOver,
+            String::from("{questions} questions played"),
+        );
+        assert_eq!(
+            render(&settings, Template::GameOver, &args),
+            "3 questions played"
+        );
+    }
+
+    #[test]
+    fn details_only_show_what_the_question_has() {
+        let mut question = Question::default();
+        assert_eq!(question_details(&question, Language::English), "");
+        question.year = Some(1999);
+        question.time_limit = Some(30);
+        assert_eq!(
+            question_details(&question, Language::English),
+            "Year: 1999\nTime limit: 30s\n"
+        );
+    }
+}
@@ -1,67 +1,525 @@
 use poise::CreateReply;
 use serenity::all::{CacheHttp, CreateMessage};
 
-pub async fn broadcast_message(
+/// Characters Discord accepts in an embed title
+const TITLE_LIMIT: usize = 256;
+/// Characters Discord accepts in an embed description
+const DESCRIPTION_LIMIT: usize = 4096;
+/// Characters Discord accepts in an embed footer
+const FOOTER_LIMIT: usize = 2048;
+/// Characters Discord accepts over all the embeds of a message
+const MESSAGE_EMBEDS_TOTAL: usize = 6000;
+/// Embeds Discord accepts in a message
+const MESSAGE_EMBEDS: usize = 10;
+/// Embeds a reply is split into before it is sent as a file instead
+const MAX_SPLIT_EMBEDS: usize = 4;
+
+/// One or several embeds, sent in as many messages as Discord's limits need
+pub struct Embeds(pub Vec<serenity::all::CreateEmbed>);
+
+impl From<serenity::all::CreateEmbed> for Embeds {
+    fn from(embed: serenity::all::CreateEmbed) -> Self {
+        Self(vec![embed])
+    }
+}
+
+impl From<Vec<serenity::all::CreateEmbed>> for Embeds {
+    fn from(embeds: Vec<serenity::all::CreateEmbed>) -> Self {
+        Self(embeds)
+    }
+}
+
+impl Embeds {
+    /// Group the embeds in messages holding at most 10 embeds and 6000 characters
+    fn messages(self) -> Vec<Vec<serenity::all::CreateEmbed>> {
+        // The json of an embed is longer than the text Discord counts, so this never
+        // under-estimates it
+        let weight =
+            |e: &serenity::all::CreateEmbed| serde_json::to_string(e).map_or(0, |s| s.len());
+        let mut messages: Vec<Vec<serenity::all::CreateEmbed>> = Vec::new();
+        let mut total = 0;
+        for embed in self.0 {
+            let w = weight(&embed);
+            match messages.last_mut() {
+                Some(last) if last.len() < MESSAGE_EMBEDS && total + w <= MESSAGE_EMBEDS_TOTAL => {
+                    total += w;
+                    last.push(embed);
+                }
+                _ => {
+                    total = w;
+                    messages.push(vec![embed]);
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// Times a message is sent before giving up on a channel
+const SEND_ATTEMPTS: u32 = 3;
+/// Channels a broadcast sends to at the same time
+const CONCURRENT_SENDS: usize = 8;
+
+/// Whether sending again could work, like after a network error or a Discord outage
+fn is_transient(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(serenity::all::HttpError::Request(_)) => true,
+        serenity::Error::Http(e) => e
+            .status_code()
+            .is_some_and(|s| s.is_server_error() || s.as_u16() == 429),
+        _ => false,
+    }
+}
+
+/// Send the messages to a channel in order, retrying the transient failures. Rate limits
+/// are waited for by serenity
+async fn send_with_retry(
     ctx: impl CacheHttp,
-    channels: impl IntoIterator<Item = serenity::model::id::ChannelId>,
-    msg: serenity::all::CreateEmbed,
-) -> Result<(), crate::Error> {
-    for c in channels {
-        c.send_message(&ctx, CreateMessage::new().embed(msg.clone()))
-            .await?;
+    channel: serenity::model::id::ChannelId,
+    messages: &[CreateMessage],
+) -> Result<(), serenity::Error> {
+    for msg in messages {
+        let mut attempt = 1;
+        loop {
+            match channel.send_message(&ctx, msg.clone()).await {
+                Ok(_) => break,
+                Err(e) if attempt < SEND_ATTEMPTS && is_transient(&e) => {
+                    tokio::time::sleep(std::time::Duration::from_millis(500 << attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
     }
     Ok(())
 }
 
+/// Send the messages to every channel at once. A channel that can't be reached doesn't
+/// stop the others, the failures are reported in the admin channel
+async fn send_all(
+    ctx: impl CacheHttp,
+    admin: Option<serenity::model::id::ChannelId>,
+    settings: &crate::settings::GuildSettings,
+    channels: &[serenity::model::id::ChannelId],
+    messages: &[CreateMessage],
+) {
+    use poise::futures_util::StreamExt;
+    let ctx = &ctx;
+    let failures = poise::futures_util::stream::iter(channels.iter().copied())
+        .map(|c| async move { (c, send_with_retry(ctx, c, messages).await) })
+        .buffer_unordered(CONCURRENT_SENDS)
+        .filter_map(|(c, result)| async move { result.err().map(|e| (c, e)) })
+        .collect::<Vec<_>>()
+        .await;
+    if failures.is_empty() {
+        return;
+    }
+    let report = failures
+        .iter()
+        .map(|(c, e)| {
+            crate::tr!(
+                settings.language,
+                "Couldn't send a message to {channel}: {error}",
+                channel = serenity::all::Mentionable::mention(c),
+                error = e
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    println!("Broadcast failures: {report}");
+    if let Some(admin) = admin.filter(|a| failures.iter().all(|(c, _)| c != a)) {
+        let msg = create_embeds(settings, crate::settings::Tone::Error, "Error!", report);
+        if let Err(e) = send_admin_message(&ctx, admin, msg).await {
+            println!("Error while reporting the broadcast failures: {:?}", e);
+        }
+    }
+}
+
+/// Something to do on Discord once the game of a guild was updated
+pub enum Outbound {
+    /// Send the messages in order to every channel
+    Send(Vec<serenity::model::id::ChannelId>, Vec<CreateMessage>),
+    /// React to a message
+    React(
+        serenity::model::id::ChannelId,
+        serenity::model::id::MessageId,
+        char,
+    ),
+}
+
+/// What a game produced while handling a command, sent once the game moved on so that
+/// Discord being slow doesn't hold it up
+#[derive(Default)]
+pub struct Outbox {
+    admin: Option<serenity::model::id::ChannelId>,
+    settings: crate::settings::GuildSettings,
+    queue: Vec<Outbound>,
+}
+
+impl Outbox {
+    pub fn new(game: &crate::config::GuildConfig) -> Self {
+        Self {
+            admin: Some(game.admin_channel),
+            settings: game.settings.clone(),
+            queue: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// What was queued so far, in order
+    pub fn queued(&self) -> &[Outbound] {
+        &self.queue
+    }
+
+    /// Queue messages for every channel
+    pub fn push(
+        &mut self,
+        channels: impl IntoIterator<Item = serenity::model::id::ChannelId>,
+        messages: Vec<CreateMessage>,
+    ) {
+        self.queue
+            .push(Outbound::Send(channels.into_iter().collect(), messages));
+    }
+
+    /// Queue embeds for every channel
+    pub fn broadcast(
+        &mut self,
+        channels: impl IntoIterator<Item = serenity::model::id::ChannelId>,
+        msg: impl Into<Embeds>,
+    ) {
+        let messages = msg
+            .into()
+            .messages()
+            .into_iter()
+            .map(|embeds| CreateMessage::new().embeds(embeds))
+            .collect();
+        self.push(channels, messages);
+    }
+
+    /// Queue embeds for the admin channel
+    pub fn admin(&mut self, msg: impl Into<Embeds>) {
+        self.broadcast(self.admin, msg);
+    }
+
+    /// Queue a reaction to a message
+    pub fn react(&mut self, message: &serenity::all::Message, emoji: char) {
+        self.queue
+            .push(Outbound::React(message.channel_id, message.id, emoji));
+    }
+
+    /// Do what was queued in order, each message being sent to all its channels at once
+    pub async fn send(self, ctx: impl CacheHttp) {
+        for outbound in &self.queue {
+            match outbound {
+                Outbound::Send(channels, messages) => {
+                    send_all(&ctx, self.admin, &self.settings, channels, messages).await
+                }
+                Outbound::React(channel, message, emoji) => {
+                    if let Err(e) = ctx
+                        .http()
+                        .create_reaction(*channel, *message, &(*emoji).into())
+                        .await
+                    {
+                        println!("Error while reacting to a message: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub async fn send_admin_message(
     ctx: impl CacheHttp,
     admin: serenity::model::id::ChannelId,
-    msg: serenity::all::CreateEmbed,
+    msg: impl Into<Embeds>,
 ) -> Result<(), crate::Error> {
-    admin
-        .send_message(&ctx, CreateMessage::new().embed(msg.clone()))
+    let messages = msg
+        .into()
+        .messages()
+        .into_iter()
+        .map(|embeds| CreateMessage::new().embeds(embeds))
+        .collect::<Vec<_>>();
+    send_with_retry(ctx, admin, &messages).await?;
+    Ok(())
+}
+
+/// Answer a press on a button
+pub async fn respond(
+    ctx: impl CacheHttp,
+    interaction: &serenity::all::ComponentInteraction,
+    msg: impl Into<Embeds>,
+    ephemeral: bool,
+) -> Result<(), crate::Error> {
+    let mut messages = msg.into().messages().into_iter();
+    interaction
+        .create_response(
+            &ctx,
+            serenity::all::CreateInteractionResponse::Message(
+                serenity::all::CreateInteractionResponseMessage::new()
+                    .embeds(messages.next().unwrap_or_default())
+                    .ephemeral(ephemeral),
+            ),
+        )
         .await?;
+    for embeds in messages {
+        interaction
+            .create_followup(
+                &ctx,
+                serenity::all::CreateInteractionResponseFollowup::new()
+                    .embeds(embeds)
+                    .ephemeral(ephemeral),
+            )
+            .await?;
+    }
     Ok(())
 }
 
+/// First `limit` characters of `text`, ending with an ellipsis when it was cut
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_owned();
+    }
+    let mut cut = text.chars().take(limit - 1).collect::<String>();
+    cut.push('…');
+    cut
+}
+
+/// Split `text` in parts of at most `limit` characters, cutting between lines when
+/// possible
+pub fn split_text(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut len = 0;
+    for line in text.split_inclusive('\n') {
+        let line_len = line.chars().count();
+        if len + line_len > limit && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+            len = 0;
+        }
+        if line_len > limit {
+            let chars = line.chars().collect::<Vec<_>>();
+            for chunk in chars.chunks(limit) {
+                parts.push(chunk.iter().collect());
+            }
+            continue;
+        }
+        current.push_str(line);
+        len += line_len;
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Characters left for the description of an embed with this title and footer
+fn description_room(title: &str, footer: &str) -> usize {
+    let used = title.chars().count().min(TITLE_LIMIT) + footer.chars().count().min(FOOTER_LIMIT);
+    DESCRIPTION_LIMIT.min(MESSAGE_EMBEDS_TOTAL - used)
+}
+
+/// Embed in the language and theme of `settings`, the title and message are translated
+/// when they are in the catalog. Text over Discord's limits is cut, use
+/// [`create_embeds`] for messages that can be long
 pub fn create_embed(
-    color: impl Into<serenity::all::Color>,
+    settings: &crate::settings::GuildSettings,
+    tone: crate::settings::Tone,
     title: impl AsRef<str>,
     message: impl AsRef<str>,
 ) -> serenity::all::CreateEmbed {
-    serenity::all::CreateEmbed::new()
-        .footer(serenity::all::CreateEmbedFooter::new(
-            "made by maiboyer with ❤️",
+    let theme = &settings.theme;
+    let title = crate::i18n::translate(settings.language, title.as_ref());
+    let embed = serenity::all::CreateEmbed::new()
+        .color(theme.colour(tone))
+        .description(truncate(
+            crate::i18n::translate(settings.language, message.as_ref()),
+            description_room(title, &theme.footer),
         ))
-        .color(color)
-        .description(message.as_ref())
-        .title(title.as_ref())
+        .title(truncate(title, TITLE_LIMIT));
+    if theme.footer.is_empty() {
+        embed
+    } else {
+        embed.footer(serenity::all::CreateEmbedFooter::new(truncate(
+            &theme.footer,
+            FOOTER_LIMIT,
+        )))
+    }
+}
+
+/// Like [`create_embed`], the message being split over several embeds when it is too
+/// long for one
+pub fn create_embeds(
+    settings: &crate::settings::GuildSettings,
+    tone: crate::settings::Tone,
+    title: impl AsRef<str>,
+    message: impl AsRef<str>,
+) -> Vec<serenity::all::CreateEmbed> {
+    let title = crate::i18n::translate(settings.language, title.as_ref());
+    let message = crate::i18n::translate(settings.language, message.as_ref());
+    // Leave room for the page number added to the title
+    let room = description_room(&format!("{title} (99/99)"), &settings.theme.footer);
+    let parts = split_text(message, room);
+    let count = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let title = if count > 1 {
+                format!("{title} ({}/{count})", i + 1)
+            } else {
+                title.to_owned()
+            };
+            create_embed(settings, tone, title, part)
+        })
+        .collect()
+}
+
+/// Reply with the message, as a file when it is too long for a few embeds
+async fn send_text(
+    ctx: poise::Context<'_, crate::Data, crate::Error>,
+    tone: crate::settings::Tone,
+    title: &str,
+    msg: &str,
+) -> Result<(), crate::Error> {
+    let settings = crate::settings::current(ctx);
+    let embeds = create_embeds(&settings, tone, title, msg);
+    if embeds.len() > MAX_SPLIT_EMBEDS {
+        ctx.send(
+            CreateReply::default()
+                .reply(true)
+                .embed(create_embed(
+                    &settings,
+                    tone,
+                    title,
+                    "The message is too long, it is attached as a file",
+                ))
+                .attachment(serenity::all::CreateAttachment::bytes(
+                    crate::i18n::translate(settings.language, msg).as_bytes(),
+                    "message.md",
+                )),
+        )
+        .await?;
+        return Ok(());
+    }
+    for (i, embeds) in Embeds(embeds).messages().into_iter().enumerate() {
+        let mut reply = CreateReply::default().reply(i == 0).ephemeral(false);
+        reply.embeds = embeds;
+        ctx.send(reply).await?;
+    }
+    Ok(())
 }
 
 pub async fn send_error(
     ctx: poise::Context<'_, crate::Data, crate::Error>,
     msg: impl AsRef<str>,
 ) -> Result<(), crate::Error> {
-    ctx.send(
-        CreateReply::default()
-            .reply(true)
-            .ephemeral(false)
-            .embed(create_embed((255, 0, 0), "Error!", msg)),
-    )
-    .await?;
-    Ok(())
+    send_text(ctx, crate::settings::Tone::Error, "Error!", msg.as_ref()).await
 }
 
 pub async fn send_reply(
     ctx: poise::Context<'_, crate::Data, crate::Error>,
     msg: impl AsRef<str>,
 ) -> Result<(), crate::Error> {
-    ctx.send(
-        CreateReply::default()
-            .reply(true)
-            .ephemeral(false)
-            .embed(create_embed((0, 255, 0), "Success!", msg)),
+    send_text(
+        ctx,
+        crate::settings::Tone::Success,
+        "Success!",
+        msg.as_ref(),
     )
-    .await?;
+    .await
+}
+
+/// Send the pages as a single embed with buttons to go from one page to another,
+/// along with the rest of `reply`
+pub async fn paginate(
+    ctx: poise::Context<'_, crate::Data, crate::Error>,
+    reply: CreateReply,
+    title: &str,
+    pages: &[String],
+) -> Result<(), crate::Error> {
+    let settings = crate::settings::current(ctx);
+    let title = crate::i18n::translate(settings.language, title);
+    // Pages too long for an embed are split in several pages
+    let room = description_room(&format!("{title} (999/999)"), &settings.theme.footer);
+    let pages = pages
+        .iter()
+        .flat_map(|p| split_text(p, room))
+        .collect::<Vec<_>>();
+    let page_embed = |i: usize| {
+        let title = if pages.len() > 1 {
+            format!("{title} ({}/{})", i + 1, pages.len())
+        } else {
+            title.to_owned()
+        };
+        create_embed(&settings, crate::settings::Tone::Success, title, &pages[i])
+    };
+    let ctx_id = ctx.id();
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+    let mut reply = reply.reply(true).embed(page_embed(0));
+    if pages.len() > 1 {
+        reply = reply.components(vec![serenity::all::CreateActionRow::Buttons(vec![
+            serenity::all::CreateButton::new(&prev_button_id).emoji('◀'),
+            serenity::all::CreateButton::new(&next_button_id).emoji('▶'),
+        ])]);
+    }
+    ctx.send(reply).await?;
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut current_page = 0;
+    while let Some(press) = serenity::all::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(std::time::Duration::from_secs(3600))
+        .await
+    {
+        if press.data.custom_id == next_button_id {
+            current_page = (current_page + 1) % pages.len();
+        } else if press.data.custom_id == prev_button_id {
+            current_page = current_page.checked_sub(1).unwrap_or(pages.len() - 1);
+        } else {
+            continue;
+        }
+        press
+            .create_response(
+                ctx.serenity_context(),
+                serenity::all::CreateInteractionResponse::UpdateMessage(
+                    serenity::all::CreateInteractionResponseMessage::new()
+                        .embed(page_embed(current_page)),
+                ),
+            )
+            .await?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_cuts_characters_with_an_ellipsis() {
+        assert_eq!(truncate("héllo", 5), "héllo");
+        assert_eq!(truncate("héllo wörld", 4), "hél…");
+        assert_eq!(truncate("héllo wörld", 4).chars().count(), 4);
+    }
+
+    #[test]
+    fn split_text_cuts_between_lines() {
+        assert_eq!(split_text("one\ntwo\nthree", 8), ["one\ntwo\n", "three"]);
+        assert_eq!(split_text("short", 8), ["short"]);
+        assert_eq!(split_text("", 8), [""]);
+    }
+
+    #[test]
+    fn split_text_cuts_long_lines_by_characters() {
+        let parts = split_text("ab\nçdéfgh\nij", 4);
+        assert_eq!(parts, ["ab\n", "çdéf", "gh\n", "ij"]);
+        assert!(parts.iter().all(|p| p.chars().count() <= 4));
+        assert_eq!(parts.concat(), "ab\nçdéfgh\nij");
+    }
+}